@@ -0,0 +1,113 @@
+// UCI-style protocol adapted for Fianco: moves are written in square
+// notation ("e2-e3") and positions are given as "startpos" or a Fianco FEN.
+use fianco_ai::{format_move, parse_move, Engine, GameState, SearchConfig, SearchInfo, TimeManager, Weights, WHITE};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+// Thinking time when `go` gives no time control
+const DEFAULT_MOVE_TIME_MS: u64 = 1000;
+
+fn main() {
+    let mut engine = Engine::new(Weights::default(), SearchConfig::default());
+    engine.set_search_callback(Some(Box::new(|info: SearchInfo| {
+        let pv: Vec<String> = info.pv.iter().map(|&mv| format_move(mv)).collect();
+        println!(
+            "info depth {} score cp {} nodes {} nps {} time {} pv {}",
+            info.depth,
+            info.score.round() as i64,
+            info.nodes,
+            info.nps,
+            info.time_ms,
+            pv.join(" ")
+        );
+        io::stdout().flush().ok();
+    })));
+    let mut state = GameState::new();
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("uci") => {
+                println!("id name fianco_ai");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                engine.reset();
+                state = GameState::new();
+            }
+            Some("position") => match set_position(&mut engine, &tokens[1..]) {
+                Ok(new_state) => state = new_state,
+                Err(err) => println!("info string {}", err),
+            },
+            Some("go") => {
+                let time_manager = time_manager(&tokens[1..], state.player);
+                let result = engine.best_move(&state.board, state.player, &time_manager);
+                match result.best_move {
+                    Some(mv) => println!("bestmove {}", format_move(mv)),
+                    None => println!("bestmove 0000"),
+                }
+            }
+            Some("quit") => break,
+            Some(command) => println!("info string unknown command {}", command),
+            None => {}
+        }
+        io::stdout().flush().ok();
+    }
+}
+
+// Handles "startpos [moves ...]" and "fen <board> <player> [moves ...]",
+// replaying the moves into the engine's game history
+fn set_position(engine: &mut Engine, tokens: &[&str]) -> Result<GameState, String> {
+    let (mut state, rest) = match tokens {
+        ["startpos", rest @ ..] => (GameState::new(), rest),
+        ["fen", board, player, rest @ ..] => {
+            let state = GameState::from_fen(&format!("{} {}", board, player)).map_err(|err| err.to_string())?;
+            (state, rest)
+        }
+        _ => return Err("expected position startpos or position fen".to_string()),
+    };
+    let moves = match rest {
+        ["moves", moves @ ..] => moves,
+        [] => &[],
+        _ => return Err("expected moves after the position".to_string()),
+    };
+
+    // The engine's history only changes once every move has been applied
+    let mut positions = vec![state.board.clone()];
+    for token in moves {
+        let mv = parse_move(token).map_err(|err| err.to_string())?;
+        state.apply_move(mv).map_err(|err| err.to_string())?;
+        positions.push(state.board.clone());
+    }
+    engine.clear_history();
+    for board in &positions {
+        engine.push_position(board);
+    }
+    engine.set_half_move_clock(state.half_move_clock);
+    Ok(state)
+}
+
+// Time control from the arguments of "go": movetime, or wtime/btime with
+// optional winc/binc and movestogo
+fn time_manager(tokens: &[&str], player: i32) -> TimeManager {
+    let value = |name: &str| -> Option<u64> {
+        let index = tokens.iter().position(|&token| token == name)?;
+        tokens.get(index + 1)?.parse().ok()
+    };
+    if let Some(move_time) = value("movetime") {
+        return TimeManager::fixed(Duration::from_millis(move_time));
+    }
+
+    let (time, increment) = if player == WHITE { ("wtime", "winc") } else { ("btime", "binc") };
+    match value(time) {
+        Some(total_time_ms) => TimeManager {
+            total_time_ms,
+            increment_ms: value(increment).unwrap_or(0),
+            moves_to_go: value("movestogo").map(|moves| moves as u32),
+            overhead_ms: 0,
+        },
+        None => TimeManager::fixed(Duration::from_millis(DEFAULT_MOVE_TIME_MS)),
+    }
+}
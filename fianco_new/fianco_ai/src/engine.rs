@@ -0,0 +1,6638 @@
+// src/engine.rs
+
+use ndarray::{s, Array2, Array3, Array4};
+use std::cmp::Reverse;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+
+pub const BOARD_SIZE: usize = 9;
+pub const EMPTY: i32 = 0;
+pub const BLACK: i32 = 1;
+pub const WHITE: i32 = -1;
+
+const WIN_SCORE: f64 = 1_000_000.0;
+const LOSE_SCORE: f64 = -1_000_000.0;
+
+// Hash key XORed in when a null move passes the turn, so that positions with
+// the same pieces but a different side to move do not share TT entries
+const NULL_MOVE_HASH_KEY: u64 = 0x9E37_79B9_7F4A_7C15;
+
+// Window width of the null-move search; wider windows mark possible PV nodes
+const NULL_WINDOW: f64 = 1.0;
+
+// Maximum search ply tracked by per-ply tables (killer moves)
+const MAX_DEPTH: usize = 64;
+
+// Forced capture extensions allowed on one path from the root
+const MAX_FORCED_EXTENSIONS: u32 = 6;
+
+// Threat extensions allowed on one path from the root
+const MAX_THREAT_EXTENSIONS: u32 = 3;
+
+// Lazy SMP helpers start up to this many plies deeper than the main thread
+const LAZY_SMP_MAX_DEPTH_OFFSET: i32 = 2;
+
+// Transposition table sizes in megabytes
+pub(crate) const DEFAULT_TT_SIZE_MB: usize = 16;
+// Default search depth, deep enough that the time limit ends most searches
+pub(crate) const DEFAULT_MAX_DEPTH: i32 = 32;
+const PARALLEL_TT_SIZE_MB: usize = 1; // Per root move in parallel search
+// Two quiet moves per ply that recently caused a beta cutoff
+type KillerTable = [[Option<Move>; 2]; MAX_DEPTH];
+
+// Triangular principal variation table: row `ply` holds the best line found
+// below the node at that ply, ending at the first None. A node's line is its
+// best move followed by the line its child left in the next row, so it never
+// depends on what the transposition table kept.
+pub(crate) struct PvTable(Vec<[Option<Move>; MAX_DEPTH]>);
+
+impl PvTable {
+    pub(crate) fn new() -> Self {
+        PvTable(vec![[None; MAX_DEPTH]; MAX_DEPTH])
+    }
+
+    // Empty the line at `ply`, on entering a node
+    fn clear(&mut self, ply: usize) {
+        if let Some(line) = self.0.get_mut(ply) {
+            line[0] = None;
+        }
+    }
+
+    // `mv` became the best move at `ply`: its line is `mv` and the child's
+    fn update(&mut self, ply: usize, mv: Move) {
+        if ply >= MAX_DEPTH {
+            return;
+        }
+        let (lines, child_lines) = self.0.split_at_mut(ply + 1);
+        let line = &mut lines[ply];
+        line[0] = Some(mv);
+        let length = child_lines.first().map_or(0, |child| {
+            let length = child[..MAX_DEPTH - 1].iter().take_while(|mv| mv.is_some()).count();
+            line[1..=length].copy_from_slice(&child[..length]);
+            length
+        });
+        if length + 1 < MAX_DEPTH {
+            line[length + 1] = None;
+        }
+    }
+
+    fn set_line(&mut self, ply: usize, moves: &[Move]) {
+        if let Some(line) = self.0.get_mut(ply) {
+            let length = moves.len().min(MAX_DEPTH);
+            for (slot, &mv) in line.iter_mut().zip(&moves[..length]) {
+                *slot = Some(mv);
+            }
+            if length < MAX_DEPTH {
+                line[length] = None;
+            }
+        }
+    }
+
+    pub(crate) fn line(&self, ply: usize) -> Vec<Move> {
+        self.0.get(ply).map_or(Vec::new(), |line| line.iter().map_while(|&mv| mv).collect())
+    }
+}
+// Accumulated cutoff scores of quiet moves
+type HistoryTable = HashMap<Move, i64>;
+// Quiet move that last refuted each opponent move
+type CounterMoveTable = HashMap<Move, Move>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from_row: usize,
+    pub from_col: usize,
+    pub to_row: usize,
+    pub to_col: usize,
+}
+
+impl Move {
+    pub const fn new(from_row: usize, from_col: usize, to_row: usize, to_col: usize) -> Self {
+        Move { from_row, from_col, to_row, to_col }
+    }
+
+    // The same move on the left-right mirrored board
+    pub fn flip(&self) -> Move {
+        Move::new(
+            self.from_row,
+            BOARD_SIZE - 1 - self.from_col,
+            self.to_row,
+            BOARD_SIZE - 1 - self.to_col,
+        )
+    }
+
+    // Move in square notation, e.g. "e2-e3"
+    pub fn to_uci_string(&self) -> String {
+        format_move(*self)
+    }
+}
+
+// Moves the search expects to be played from the root position onwards
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrincipalVariation {
+    pub moves: Vec<Move>,
+}
+
+impl PrincipalVariation {
+    // Space-separated moves, e.g. "e2-e3 e8-e7"
+    pub fn to_uci_string(&self) -> String {
+        self.moves
+            .iter()
+            .map(|&mv| format_move(mv))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+}
+
+// Outcome of a search: the best move, its evaluation and the principal
+// variation, with statistics
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: f64,
+    pub pv: PrincipalVariation,
+    pub depth_reached: i32, // Deepest completed iteration
+    pub nodes: u64,         // Including quiescence nodes
+    pub q_nodes: u64,
+    pub time_ms: u64,
+    pub tt_hits: u64,
+    pub tt_size: usize, // Transposition table entries after the search
+    pub depth_stats: IterativeDeepeningStats,
+}
+
+// A completed iterative deepening iteration. The counts are totals since the
+// start of the search.
+#[derive(Debug, Clone)]
+pub struct DepthData {
+    pub depth: i32,
+    pub score: f64,
+    pub best_move: Move,
+    pub nodes: u64,
+    pub time_ms: u64,
+    pub tt_hits: u64,
+}
+
+// Per-iteration results, shallowest first
+#[derive(Debug, Clone, Default)]
+pub struct IterativeDeepeningStats {
+    pub depth_data: Vec<DepthData>,
+}
+
+impl SearchResult {
+    pub(crate) fn new(best_move: Option<Move>, score: f64, pv: PrincipalVariation, depth_reached: i32) -> Self {
+        SearchResult { best_move, score, pv, depth_reached, ..SearchResult::default() }
+    }
+
+    fn record_stats(&mut self, clock: &SearchClock, transposition_table: &dyn TranspositionStore) {
+        let stats = clock.stats();
+        self.nodes = stats.total_nodes();
+        self.q_nodes = stats.q_nodes;
+        self.time_ms = stats.elapsed.as_millis() as u64;
+        self.tt_hits = stats.tt_hits;
+        self.tt_size = transposition_table.len();
+    }
+}
+
+// Transposition Table Entry
+#[derive(Clone, Copy)]
+pub(crate) struct TTEntry {
+    key: u64, // Full Zobrist hash, to tell apart positions sharing a bucket
+    depth: i32,
+    value: f64,
+    flag: NodeType,
+    best_move: Option<Move>,
+    #[cfg(feature = "hash_stats")]
+    position: [u8; COMPRESSED_BOARD_SIZE], // Stored position, to detect key collisions
+}
+
+#[derive(Clone, Copy)]
+enum NodeType {
+    Exact,
+    LowerBound,
+    UpperBound,
+}
+
+// Fixed-size transposition table. Each bucket holds a depth-preferred slot,
+// only replaced by searches at least as deep, and an always-replace slot
+// that takes everything else.
+struct TTable {
+    buckets: Vec<[Option<TTEntry>; 2]>,
+}
+
+impl TTable {
+    fn new(size_mb: usize) -> Self {
+        let bucket_size = std::mem::size_of::<[Option<TTEntry>; 2]>();
+        let num_buckets = (size_mb * 1024 * 1024 / bucket_size).max(1);
+        TTable {
+            buckets: vec![[None; 2]; num_buckets],
+        }
+    }
+
+    fn bucket_index(&self, hash: u64) -> usize {
+        (hash % self.buckets.len() as u64) as usize
+    }
+
+    fn probe(&self, hash: u64) -> Option<&TTEntry> {
+        self.buckets[self.bucket_index(hash)]
+            .iter()
+            .flatten()
+            .find(|entry| entry.key == hash)
+    }
+
+    fn store(&mut self, hash: u64, entry: TTEntry) {
+        let entry = TTEntry { key: hash, ..entry };
+        let index = self.bucket_index(hash);
+        let bucket = &mut self.buckets[index];
+        let replace_deep = match bucket[0] {
+            Some(existing) => existing.key == hash || entry.depth >= existing.depth,
+            None => true,
+        };
+        if replace_deep {
+            bucket[0] = Some(entry);
+        } else {
+            bucket[1] = Some(entry);
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &TTEntry> {
+        self.buckets.iter().flat_map(|bucket| bucket.iter().flatten())
+    }
+
+    fn clear(&mut self) {
+        self.buckets.fill([None; 2]);
+    }
+}
+
+// Transposition table key for a position. With symmetry enabled a position
+// and its mirror image share the lower of their two hashes, and entries are
+// stored oriented to that position, so the mirrored one flips their moves.
+struct TTKey {
+    hash: u64,
+    mirrored: bool,
+    #[cfg(feature = "hash_stats")]
+    position: [u8; COMPRESSED_BOARD_SIZE], // In the stored orientation
+}
+
+impl TTKey {
+    fn new(board: &BitBoard, zobrist_hash: u64, zobrist_table: &ZobristTable, use_symmetry: bool) -> Self {
+        if use_symmetry {
+            // The one place the search hashes from scratch: the mirror hash
+            // can't be derived from the incremental one. Keep any null-move
+            // pass key so passed positions stay apart from real ones.
+            let pass_key = zobrist_hash ^ board.zobrist_hash(zobrist_table);
+            let mirror_hash = board.flip().zobrist_hash(zobrist_table) ^ pass_key;
+            if mirror_hash < zobrist_hash {
+                return TTKey {
+                    hash: mirror_hash,
+                    mirrored: true,
+                    #[cfg(feature = "hash_stats")]
+                    position: compress_board(&board.flip().to_array2()),
+                };
+            }
+        }
+        TTKey {
+            hash: zobrist_hash,
+            mirrored: false,
+            #[cfg(feature = "hash_stats")]
+            position: compress_board(&board.to_array2()),
+        }
+    }
+
+    // Converts an entry between this position's orientation and the stored
+    // one; flipping is its own inverse so this works both ways
+    fn orient(&self, entry: TTEntry) -> TTEntry {
+        if self.mirrored {
+            TTEntry { best_move: entry.best_move.map(|mv| mv.flip()), ..entry }
+        } else {
+            entry
+        }
+    }
+}
+
+// Marks the end of the recency list in `LruTTable`
+const LRU_NIL: usize = usize::MAX;
+
+struct LruNode {
+    entry: TTEntry,
+    prev: usize, // More recently used neighbour
+    next: usize, // Less recently used neighbour
+}
+
+// Transposition table holding at most `capacity` entries, evicting the least
+// recently probed or stored one when full. Entries live in a slab threaded
+// onto a doubly-linked recency list, most recent at `head`.
+struct LruTTable {
+    capacity: usize,
+    map: HashMap<u64, usize>, // Hash to slab index
+    nodes: Vec<LruNode>,
+    head: usize,
+    tail: usize,
+}
+
+impl LruTTable {
+    fn new(size_mb: usize) -> Self {
+        // Count the map's key and index, doubled for its spare capacity
+        let entry_size = std::mem::size_of::<LruNode>() + 2 * std::mem::size_of::<(u64, usize)>();
+        let capacity = (size_mb * 1024 * 1024 / entry_size).max(1);
+        LruTTable {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            nodes: Vec::with_capacity(capacity),
+            head: LRU_NIL,
+            tail: LRU_NIL,
+        }
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (prev, next) = (self.nodes[index].prev, self.nodes[index].next);
+        match prev {
+            LRU_NIL => self.head = next,
+            _ => self.nodes[prev].next = next,
+        }
+        match next {
+            LRU_NIL => self.tail = prev,
+            _ => self.nodes[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, index: usize) {
+        self.nodes[index].prev = LRU_NIL;
+        self.nodes[index].next = self.head;
+        match self.head {
+            LRU_NIL => self.tail = index,
+            head => self.nodes[head].prev = index,
+        }
+        self.head = index;
+    }
+
+    fn touch(&mut self, index: usize) {
+        if self.head != index {
+            self.unlink(index);
+            self.push_front(index);
+        }
+    }
+}
+
+// Transposition table access used by the search, so it can run on a table of
+// its own or on one shared between threads
+pub(crate) trait TranspositionStore: Send {
+    fn probe(&mut self, hash: u64) -> Option<TTEntry>;
+    fn store(&mut self, hash: u64, entry: TTEntry);
+    fn clear(&mut self);
+    fn len(&self) -> usize; // Entries currently stored
+}
+
+impl TranspositionStore for TTable {
+    fn probe(&mut self, hash: u64) -> Option<TTEntry> {
+        TTable::probe(self, hash).copied()
+    }
+
+    fn store(&mut self, hash: u64, entry: TTEntry) {
+        TTable::store(self, hash, entry);
+    }
+
+    fn clear(&mut self) {
+        TTable::clear(self);
+    }
+
+    fn len(&self) -> usize {
+        self.entries().count()
+    }
+}
+
+impl TranspositionStore for LruTTable {
+    fn probe(&mut self, hash: u64) -> Option<TTEntry> {
+        let index = *self.map.get(&hash)?;
+        self.touch(index);
+        Some(self.nodes[index].entry)
+    }
+
+    fn store(&mut self, hash: u64, entry: TTEntry) {
+        let entry = TTEntry { key: hash, ..entry };
+        if let Some(&index) = self.map.get(&hash) {
+            self.nodes[index].entry = entry;
+            self.touch(index);
+            return;
+        }
+
+        let index = if self.nodes.len() < self.capacity {
+            self.nodes.push(LruNode { entry, prev: LRU_NIL, next: LRU_NIL });
+            self.nodes.len() - 1
+        } else {
+            // Reuse the least recently used slot
+            let index = self.tail;
+            self.unlink(index);
+            self.map.remove(&self.nodes[index].entry.key);
+            self.nodes[index].entry = entry;
+            index
+        };
+        self.push_front(index);
+        self.map.insert(hash, index);
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.nodes.clear();
+        self.head = LRU_NIL;
+        self.tail = LRU_NIL;
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+// Handle to a table shared by Lazy SMP search threads
+#[derive(Clone, Copy)]
+struct SharedTTable<'a, 'b>(&'a Mutex<&'b mut dyn TranspositionStore>);
+
+impl TranspositionStore for SharedTTable<'_, '_> {
+    fn probe(&mut self, hash: u64) -> Option<TTEntry> {
+        self.0.lock().unwrap().probe(hash)
+    }
+
+    fn store(&mut self, hash: u64, entry: TTEntry) {
+        self.0.lock().unwrap().store(hash, entry);
+    }
+
+    fn clear(&mut self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+}
+
+// The engine's main table: the bucketed `TTable`, or an `LruTTable` when
+// `config.lru_tt` is set
+pub(crate) fn new_transposition_table(config: &SearchConfig) -> Box<dyn TranspositionStore> {
+    if config.lru_tt {
+        Box::new(LruTTable::new(config.tt_size_mb))
+    } else {
+        Box::new(TTable::new(config.tt_size_mb))
+    }
+}
+
+// Positional value of a piece on each square, from its owner's point of view:
+// row 0 is the owner's back row, so one table serves both sides
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceSquareTable {
+    pub values: [[f64; BOARD_SIZE]; BOARD_SIZE],
+}
+
+impl PieceSquareTable {
+    // The table matching the linear advancement and edge terms of `weights`
+    pub fn from_weights(weights: &Weights) -> Self {
+        let mut values = [[0.0; BOARD_SIZE]; BOARD_SIZE];
+        for (row, row_values) in values.iter_mut().enumerate() {
+            for (col, value) in row_values.iter_mut().enumerate() {
+                *value = weights.advancement_value * row as f64;
+                if is_edge_square(row, col) {
+                    *value += weights.edge_pawn_bonus;
+                }
+            }
+        }
+        PieceSquareTable { values }
+    }
+
+    fn value(&self, row: usize, col: usize, owner: i32) -> f64 {
+        let relative_row = if owner == BLACK { row } else { BOARD_SIZE - 1 - row };
+        self.values[relative_row][col]
+    }
+
+    // Table values of `player`'s pieces minus those of the opponent's
+    pub fn eval(&self, board: &Array2<i32>, player: i32) -> f64 {
+        board
+            .indexed_iter()
+            .filter(|&(_, &piece)| piece != EMPTY)
+            .map(|((row, col), &piece)| {
+                let value = self.value(row, col, piece);
+                if piece == player { value } else { -value }
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Weights {
+    pub piece_value: f64,
+    pub advancement_value: f64,
+    pub unstoppable_pawn_bonus: f64,
+    pub opponent_unstoppable_pawn_penalty: f64,
+    pub center_control_value: f64,
+    pub mobility_value: f64,
+    pub edge_pawn_bonus: f64,
+    pub connectivity_value: f64,
+    pub blocked_pawn_penalty: f64,
+    pub threatened_penalty: f64,
+    pub promotion_race_bonus: f64,
+    pub promotion_proximity_value: f64,
+    // Search score adjustment for revisiting a position, see
+    // `repetition_penalty`
+    pub repetition_penalty: f64,
+    // Replaces the advancement and edge terms when set
+    pub pst: Option<PieceSquareTable>,
+    // Add more weights as needed
+}
+
+impl Default for Weights {
+    // Tuned weights from best_weights.pkl
+    fn default() -> Self {
+        Weights {
+            piece_value: 25.72775211023481,
+            advancement_value: 12.223569973991484,
+            unstoppable_pawn_bonus: 7111.317123060887,
+            opponent_unstoppable_pawn_penalty: -7111.317123060887,
+            center_control_value: 1.1042082332211591,
+            mobility_value: 7.888485605822991,
+            edge_pawn_bonus: 13.035577407904977,
+            connectivity_value: 0.0,
+            blocked_pawn_penalty: 0.0,
+            threatened_penalty: 0.0,
+            promotion_race_bonus: 0.0,
+            promotion_proximity_value: 0.0,
+            repetition_penalty: 0.0,
+            pst: None,
+        }
+    }
+}
+
+impl Weights {
+    // Names of the scalar weights, in the order of `values_mut`
+    pub(crate) const NAMES: [&'static str; 13] = [
+        "piece_value",
+        "advancement_value",
+        "unstoppable_pawn_bonus",
+        "opponent_unstoppable_pawn_penalty",
+        "center_control_value",
+        "mobility_value",
+        "edge_pawn_bonus",
+        "connectivity_value",
+        "blocked_pawn_penalty",
+        "threatened_penalty",
+        "promotion_race_bonus",
+        "promotion_proximity_value",
+        "repetition_penalty",
+    ];
+
+    pub(crate) fn values_mut(&mut self) -> [&mut f64; 13] {
+        [
+            &mut self.piece_value,
+            &mut self.advancement_value,
+            &mut self.unstoppable_pawn_bonus,
+            &mut self.opponent_unstoppable_pawn_penalty,
+            &mut self.center_control_value,
+            &mut self.mobility_value,
+            &mut self.edge_pawn_bonus,
+            &mut self.connectivity_value,
+            &mut self.blocked_pawn_penalty,
+            &mut self.threatened_penalty,
+            &mut self.promotion_race_bonus,
+            &mut self.promotion_proximity_value,
+            &mut self.repetition_penalty,
+        ]
+    }
+
+    // Weights for `evaluate_endgame`
+    pub fn endgame(&self) -> Weights {
+        Weights {
+            advancement_value: self.advancement_value * ENDGAME_ADVANCEMENT_SCALE,
+            mobility_value: self.mobility_value * ENDGAME_MOBILITY_SCALE,
+            ..self.clone()
+        }
+    }
+
+    // Save as a flat JSON object keyed by field name. The piece-square table
+    // is not saved. JSON has no infinities or NaN, so those are rejected.
+    pub fn to_json_file(&self, path: &str) -> io::Result<()> {
+        let mut values = self.clone();
+        let mut fields = Vec::new();
+        for (name, value) in Weights::NAMES.iter().zip(values.values_mut()) {
+            if !value.is_finite() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("weight {} is {}, which JSON cannot represent", name, value),
+                ));
+            }
+            fields.push(format!("  \"{}\": {:?}", name, value));
+        }
+        fs::write(path, format!("{{\n{}\n}}\n", fields.join(",\n")))
+    }
+
+    // Load a file written by `to_json_file`. Every field must be present.
+    pub fn from_json_file(path: &str) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let text = fs::read_to_string(path)?;
+        let body = text
+            .trim()
+            .strip_prefix('{')
+            .and_then(|text| text.strip_suffix('}'))
+            .ok_or_else(|| invalid("weights file is not a JSON object".to_string()))?;
+
+        let mut parsed = HashMap::new();
+        for field in body.split(',').filter(|field| !field.trim().is_empty()) {
+            let (key, value) = field
+                .split_once(':')
+                .ok_or_else(|| invalid(format!("malformed weights entry: {}", field.trim())))?;
+            let key = key.trim().trim_matches('"');
+            let value: f64 = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid(format!("weight {} is not a number", key)))?;
+            if !Weights::NAMES.contains(&key) {
+                return Err(invalid(format!("unknown weight {}", key)));
+            }
+            parsed.insert(key.to_string(), value);
+        }
+
+        let mut weights = Weights::default();
+        for (name, value) in Weights::NAMES.iter().zip(weights.values_mut()) {
+            *value = *parsed.get(*name).ok_or_else(|| invalid(format!("missing weight {}", name)))?;
+        }
+        Ok(weights)
+    }
+}
+
+// Null-move pruning settings
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "python", derive(pyo3::FromPyObject))]
+pub struct NullMoveConfig {
+    pub reduction: i32,   // Extra depth reduction (R) applied to the null-move search
+    pub min_pieces: usize, // Skip null moves below this many pieces (zugzwang risk)
+}
+
+// Late move reduction settings
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "python", derive(pyo3::FromPyObject))]
+pub struct LmrConfig {
+    pub full_depth_moves: usize, // Moves searched at full depth before reducing
+    pub reduction_limit: i32,    // Maximum number of plies a move can be reduced by
+}
+
+// Zobrist keys for `search_fixed_depth`
+const FIXED_DEPTH_ZOBRIST_SEED: u64 = 0;
+
+// Search to exactly `depth` plies with no time limit. The Zobrist keys are
+// fixed too, so repeated runs visit the same tree.
+pub fn search_fixed_depth(board: &Array2<i32>, player: i32, evaluator: &dyn Evaluator, depth: i32) -> SearchResult {
+    let config = SearchConfig { max_depth: depth, ..SearchConfig::default() };
+    let zobrist_table = ZobristTable::new_seeded(FIXED_DEPTH_ZOBRIST_SEED);
+    let mut transposition_table = new_transposition_table(&config);
+    let clock = SearchClock::new(Duration::MAX, None);
+    iterative_deepening(
+        board,
+        player,
+        evaluator,
+        &config,
+        &zobrist_table,
+        transposition_table.as_mut(),
+        &[],
+        0,
+        &clock,
+        None,
+    )
+}
+
+// Entries kept in memory by a search log; later nodes only go to the file
+const MAX_SEARCH_LOG_ENTRIES: usize = 100_000;
+
+// A searched node: the move leading to it, its window and result
+#[derive(Debug, Clone)]
+pub struct SearchLogEntry {
+    pub depth: i32,
+    pub mv: Option<Move>, // None at the root
+    pub alpha: f64,
+    pub beta: f64,
+    pub eval: f64,
+    pub cutoff: bool, // The node failed high
+}
+
+impl SearchLogEntry {
+    // One JSON object. Infinite bounds are written as Infinity / -Infinity,
+    // which Python's json module accepts.
+    pub fn to_json(&self) -> String {
+        let number = |value: f64| match value {
+            f64::INFINITY => "Infinity".to_string(),
+            f64::NEG_INFINITY => "-Infinity".to_string(),
+            value => format!("{:?}", value),
+        };
+        let mv = self.mv.map_or("null".to_string(), |mv| format!("\"{}\"", format_move(mv)));
+        format!(
+            "{{\"depth\": {}, \"mv\": {}, \"alpha\": {}, \"beta\": {}, \"eval\": {}, \"cutoff\": {}}}",
+            self.depth,
+            mv,
+            number(self.alpha),
+            number(self.beta),
+            number(self.eval),
+            self.cutoff,
+        )
+    }
+}
+
+// Search tree log: every entry is written to the file as a line of JSON and
+// the first MAX_SEARCH_LOG_ENTRIES are also kept in memory. Writing stops
+// at the first I/O error.
+struct SearchLog {
+    writer: Option<BufWriter<fs::File>>,
+    entries: Vec<SearchLogEntry>,
+}
+
+impl SearchLog {
+    fn record(&mut self, entry: SearchLogEntry) {
+        if let Some(writer) = &mut self.writer {
+            if writeln!(writer, "{}", entry.to_json()).is_err() {
+                self.writer = None;
+            }
+        }
+        if self.entries.len() < MAX_SEARCH_LOG_ENTRIES {
+            self.entries.push(entry);
+        }
+    }
+}
+
+// Time control for a search: it ends once `time_limit` has passed since
+// `start_time` or `stop` has been raised. Also counts the nodes visited,
+// atomically since parallel root search shares the clock between threads,
+// and holds the search tree log if there is one.
+pub(crate) struct SearchClock {
+    start_time: Instant,
+    time_limit: Duration,
+    stop: Option<Arc<AtomicBool>>,
+    ignore_time_limit: AtomicBool, // Only the stop flag ends the search
+    nodes: AtomicU64,
+    q_nodes: AtomicU64, // Quiescence nodes, not included in `nodes`
+    tt_hits: AtomicU64,
+    log: Option<Mutex<SearchLog>>,
+    #[cfg(feature = "hash_stats")]
+    hash_stats: HashStats,
+}
+
+// Transposition lookups, and those finding an entry with the same key but a
+// different position. Only collected with the `hash_stats` feature.
+#[cfg(feature = "hash_stats")]
+#[derive(Debug, Default)]
+pub(crate) struct HashStats {
+    collisions: AtomicU64,
+    lookups: AtomicU64,
+}
+
+#[cfg(feature = "hash_stats")]
+impl HashStats {
+    fn record_lookup(&self, collision: bool) {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        self.collisions.fetch_add(collision as u64, Ordering::Relaxed);
+    }
+
+    fn add(&self, other: &HashStats) {
+        self.collisions.fetch_add(other.collisions.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.lookups.fetch_add(other.lookups.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    // Share of lookups that were collisions; 0 before any lookup
+    fn collision_rate(&self) -> f64 {
+        let lookups = self.lookups.load(Ordering::Relaxed);
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.collisions.load(Ordering::Relaxed) as f64 / lookups as f64
+    }
+
+    fn clear(&self) {
+        self.collisions.store(0, Ordering::Relaxed);
+        self.lookups.store(0, Ordering::Relaxed);
+    }
+}
+
+impl SearchClock {
+    pub(crate) fn new(time_limit: Duration, stop: Option<Arc<AtomicBool>>) -> Self {
+        SearchClock {
+            start_time: Instant::now(),
+            time_limit,
+            stop,
+            ignore_time_limit: AtomicBool::new(false),
+            nodes: AtomicU64::new(0),
+            q_nodes: AtomicU64::new(0),
+            tt_hits: AtomicU64::new(0),
+            log: None,
+            #[cfg(feature = "hash_stats")]
+            hash_stats: HashStats::default(),
+        }
+    }
+
+    // Log the searched tree to `path`. If the file cannot be created the
+    // entries are only kept in memory.
+    pub(crate) fn with_log(mut self, path: Option<&Path>) -> Self {
+        self.log = path.map(|path| {
+            let writer = fs::File::create(path).ok().map(BufWriter::new);
+            Mutex::new(SearchLog { writer, entries: Vec::new() })
+        });
+        self
+    }
+
+    // `entry` is only built when logging
+    fn log_node(&self, entry: impl FnOnce() -> SearchLogEntry) {
+        if let Some(log) = &self.log {
+            log.lock().unwrap().record(entry());
+        }
+    }
+
+    // Entries kept in memory, flushing the log file
+    pub(crate) fn into_log_entries(self) -> Option<Vec<SearchLogEntry>> {
+        self.log.map(|log| log.into_inner().unwrap().entries)
+    }
+
+    // Past `fraction` of the time limit, or stopped
+    fn soft_expired(&self, fraction: f64) -> bool {
+        self.start_time.elapsed().as_secs_f64() >= self.time_limit.as_secs_f64() * fraction
+            || self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn set_ignore_time_limit(&self, ignore: bool) {
+        self.ignore_time_limit.store(ignore, Ordering::Relaxed);
+    }
+
+    fn expired(&self) -> bool {
+        (!self.ignore_time_limit.load(Ordering::Relaxed) && self.start_time.elapsed() >= self.time_limit)
+            || self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn count_node(&self) {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn count_q_node(&self) {
+        self.q_nodes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn count_tt_hit(&self) {
+        self.tt_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Fold in the counts of a helper search
+    fn add_counts(&self, other: &SearchClock) {
+        self.nodes.fetch_add(other.nodes.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.q_nodes.fetch_add(other.q_nodes.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.tt_hits.fetch_add(other.tt_hits.load(Ordering::Relaxed), Ordering::Relaxed);
+        #[cfg(feature = "hash_stats")]
+        self.hash_stats.add(&other.hash_stats);
+    }
+
+    fn stats(&self) -> SearchStats {
+        SearchStats {
+            nodes: self.nodes.load(Ordering::Relaxed),
+            q_nodes: self.q_nodes.load(Ordering::Relaxed),
+            tt_hits: self.tt_hits.load(Ordering::Relaxed),
+            elapsed: self.start_time.elapsed(),
+        }
+    }
+}
+
+// Counts of a finished search
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchStats {
+    pub nodes: u64,   // Nodes visited by the main search
+    pub q_nodes: u64, // Nodes visited by quiescence search
+    pub tt_hits: u64, // Transposition table probes that found the position
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    pub fn total_nodes(&self) -> u64 {
+        self.nodes + self.q_nodes
+    }
+
+    // Nodes per second over both searches
+    pub fn nps(&self) -> u64 {
+        (self.total_nodes() as f64 / self.elapsed.as_secs_f64().max(1e-3)) as u64
+    }
+}
+
+// Progress report sent after each completed iterative deepening iteration
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: i32,
+    pub score: f64,
+    pub nodes: u64,
+    pub nps: u64, // Nodes per second
+    pub pv: Vec<Move>,
+    pub time_ms: u64,
+}
+
+impl SearchInfo {
+    fn new(depth: i32, score: f64, pv: &PrincipalVariation, clock: &SearchClock) -> Self {
+        let stats = clock.stats();
+        SearchInfo {
+            depth,
+            score,
+            nodes: stats.total_nodes(),
+            nps: stats.nps(),
+            pv: pv.moves.clone(),
+            time_ms: stats.elapsed.as_millis() as u64,
+        }
+    }
+}
+
+// Receives a `SearchInfo` per iteration. Shared with search threads, hence
+// Send + Sync.
+pub type SearchCallbackFn = dyn Fn(SearchInfo) + Send + Sync;
+pub type SearchCallback = Box<SearchCallbackFn>;
+
+// Iterative deepening driver shared by `negamax` and `Engine`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn iterative_deepening(
+    board: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
+    zobrist_table: &ZobristTable,
+    transposition_table: &mut dyn TranspositionStore,
+    position_history: &[u64], // Hashes of positions played so far in the game
+    half_move_clock: u32, // Plies since the last capture or forward move
+    clock: &SearchClock, // Also collects the node counts of all search threads
+    search_callback: Option<&SearchCallbackFn>,
+) -> SearchResult {
+    let mut result = if let Some(beam_width) = config.beam_width {
+        beam_search(board, player, evaluator, config, beam_width, zobrist_table, transposition_table, clock)
+    } else if config.threads > 1 {
+        lazy_smp_search(
+            board,
+            player,
+            evaluator,
+            config,
+            zobrist_table,
+            transposition_table,
+            position_history,
+            half_move_clock,
+            clock,
+            search_callback,
+        )
+    } else {
+        search_iterations(
+            board,
+            player,
+            evaluator,
+            config,
+            zobrist_table,
+            transposition_table,
+            position_history,
+            half_move_clock,
+            clock,
+            1,
+            None,
+            search_callback,
+        )
+    };
+    result.record_stats(clock, transposition_table);
+    result
+}
+
+// Lazy SMP: the main thread runs the usual iterative deepening while helper
+// threads search the same position on a shared transposition table, each
+// starting a little deeper and with a different first root move. The result
+// of the deepest completed iteration wins, ties going to the main thread.
+#[allow(clippy::too_many_arguments)]
+fn lazy_smp_search(
+    board: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
+    zobrist_table: &ZobristTable,
+    transposition_table: &mut dyn TranspositionStore,
+    position_history: &[u64],
+    half_move_clock: u32,
+    clock: &SearchClock,
+    search_callback: Option<&SearchCallbackFn>,
+) -> SearchResult {
+    let shared_table = Mutex::new(transposition_table);
+    let shared = SharedTTable(&shared_table);
+    let stop = Arc::new(AtomicBool::new(false));
+    let root_moves = get_valid_moves(board, player);
+    // Helpers must not start their own thread pools
+    let helper_config = SearchConfig { parallel: false, threads: 1, ..config.clone() };
+
+    let (main_result, helper_results) = thread::scope(|scope| {
+        let mut rng = rand::thread_rng();
+        let helpers: Vec<_> = (1..config.threads)
+            .map(|_| {
+                let mut helper_table = shared;
+                let helper_clock = SearchClock::new(Duration::MAX, Some(Arc::clone(&stop)));
+                let start_depth = 1 + rng.gen_range(0..=LAZY_SMP_MAX_DEPTH_OFFSET);
+                let first_move = (!root_moves.is_empty()).then(|| root_moves[rng.gen_range(0..root_moves.len())]);
+                let helper_config = &helper_config;
+                scope.spawn(move || {
+                    let result = search_iterations(
+                        board,
+                        player,
+                        evaluator,
+                        helper_config,
+                        zobrist_table,
+                        &mut helper_table,
+                        position_history,
+                        half_move_clock,
+                        &helper_clock,
+                        start_depth,
+                        first_move,
+                        None,
+                    );
+                    (result, helper_clock)
+                })
+            })
+            .collect();
+
+        let mut main_table = shared;
+        let main_result = search_iterations(
+            board,
+            player,
+            evaluator,
+            config,
+            zobrist_table,
+            &mut main_table,
+            position_history,
+            half_move_clock,
+            clock,
+            1,
+            None,
+            search_callback,
+        );
+        stop.store(true, Ordering::Relaxed);
+
+        let helper_results: Vec<_> = helpers
+            .into_iter()
+            .map(|helper| {
+                let (result, helper_clock) = helper.join().expect("search thread panicked");
+                clock.add_counts(&helper_clock);
+                result
+            })
+            .collect();
+        (main_result, helper_results)
+    });
+
+    let mut best = main_result;
+    for result in helper_results {
+        if result.depth_reached > best.depth_reached && result.best_move.is_some() {
+            best = result;
+        }
+    }
+    best
+}
+
+// The iterative deepening loop itself, starting at `start_depth` and trying
+// `first_move` first at the root until an iteration finds a best move.
+// Reports each completed iteration to `search_callback`. Statistics are left
+// for the caller to record.
+#[allow(clippy::too_many_arguments)]
+fn search_iterations(
+    board: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
+    zobrist_table: &ZobristTable,
+    transposition_table: &mut dyn TranspositionStore,
+    position_history: &[u64],
+    half_move_clock: u32,
+    clock: &SearchClock,
+    start_depth: i32,
+    first_move: Option<Move>,
+    search_callback: Option<&SearchCallbackFn>,
+) -> SearchResult {
+    // Search on the bitboard representation
+    let bitboard = BitBoard::from_array2(board);
+
+    // Compute initial hash
+    let initial_hash = bitboard.zobrist_hash(zobrist_table);
+
+    // Positions on the search path for threefold repetition detection
+    let mut zobrist_stack = ZobristStack::new();
+
+    // Initialize killer moves, shared across iterations
+    let mut killers: KillerTable = [[None; 2]; MAX_DEPTH];
+
+    // Principal variation table, refilled by every iteration
+    let mut pv_table = PvTable::new();
+
+    // Initialize history table, shared across iterations
+    let mut history: HistoryTable = HashMap::new();
+
+    // Initialize counter-move table, shared across iterations
+    let mut counter_moves: CounterMoveTable = HashMap::new();
+
+    // Initialize gain table, shared across iterations
+    let mut gain_table = GainTable::new();
+
+    let mut best_move = None;
+    let mut evaluation = 0.0;
+    let mut pv = PrincipalVariation::default();
+    let mut prev_score: Option<f64> = None;
+
+    // Get all valid moves in the current position (captures if any)
+    let moves = get_valid_moves(board, player);
+
+    if moves.len() == 1 {
+        // Only one legal move, play it immediately
+        let mv = moves[0];
+        let evaluation = static_eval(&bitboard, player, evaluator, true);
+        return SearchResult::new(Some(mv), evaluation, PrincipalVariation { moves: vec![mv] }, 0);
+    }
+
+    // Depth 0 answer in case the time runs out before depth 1 completes
+    let fallback = static_move_scores(&bitboard, player, evaluator).first().copied();
+
+    let mut completed_depth = 0;
+    let mut depth_stats = IterativeDeepeningStats::default();
+
+    // Depths completed regardless of the time limit
+    let min_depth = config.min_depth.min(config.max_depth);
+
+    // Iterative Deepening Loop
+    for depth in start_depth..=config.max_depth {
+        clock.set_ignore_time_limit(depth <= min_depth);
+
+        // Check if time limit exceeded
+        if clock.expired() {
+            break;
+        }
+
+        // Age history scores so older iterations do not dominate
+        for score in history.values_mut() {
+            *score /= 2;
+        }
+
+        // Aspiration window around the previous iteration's score
+        let (mut alpha, mut beta) = match (config.aspiration_delta, prev_score) {
+            (Some(delta), Some(score)) => (score - delta, score + delta),
+            _ => (f64::NEG_INFINITY, f64::INFINITY),
+        };
+
+        let (eval, mv) = loop {
+            // Reset position counts for each search
+            seed_zobrist_stack(&mut zobrist_stack, position_history, initial_hash);
+
+            let result = if config.parallel {
+                parallel_root_search(
+                    &bitboard,
+                    depth,
+                    player,
+                    alpha,
+                    beta,
+                    evaluator,
+                    depth >= 2,
+                    initial_hash,
+                    zobrist_table,
+                    transposition_table,
+                    config.use_symmetry,
+                    &zobrist_stack,
+                    clock,
+                    best_move.or(first_move),
+                    config.null_move.as_ref(),
+                    config.lmr.as_ref(),
+                    config.lmp_threshold,
+                    config.futility_margin,
+                    config.razoring_margin,
+                    config.delta_margin,
+                    &mut killers,
+                    &mut pv_table,
+                    if config.use_history { Some(&mut history) } else { None },
+                    if config.use_counter_moves { Some(&mut counter_moves) } else { None },
+                    if config.use_gain_table { Some(&mut gain_table) } else { None },
+                    half_move_clock,
+                    config.use_extensions,
+                    config.use_forced_capture_extension,
+                    config.use_threat_extension,
+                )
+            } else {
+                negamax_search(
+                    &bitboard,
+                    depth,
+                    player,
+                    alpha,
+                    beta,
+                    evaluator,
+                    depth >= 2,
+                    initial_hash,
+                    zobrist_table,
+                    transposition_table,
+                    config.use_symmetry,
+                    &mut zobrist_stack,
+                    clock,
+                    best_move.or(first_move), // Pass the best move from previous iteration
+                    config.null_move.as_ref(),
+                    config.lmr.as_ref(),
+                    config.lmp_threshold,
+                    config.futility_margin,
+                    config.razoring_margin,
+                    config.delta_margin,
+                    &mut killers,
+                    &mut pv_table,
+                    if config.use_history { Some(&mut history) } else { None },
+                    if config.use_counter_moves { Some(&mut counter_moves) } else { None },
+                    if config.use_gain_table { Some(&mut gain_table) } else { None },
+                    None,
+                    0,
+                    half_move_clock,
+                    false,
+                    config.use_extensions,
+                    config.use_forced_capture_extension,
+                    &mut 0,
+                    config.use_threat_extension,
+                    &mut 0,
+                )
+            };
+
+            if clock.expired() {
+                break result;
+            }
+
+            // On a fail-low or fail-high, open that side of the window and
+            // search again; a second failure ends up with the full window
+            if result.0 <= alpha && alpha > f64::NEG_INFINITY {
+                alpha = f64::NEG_INFINITY;
+            } else if result.0 >= beta && beta < f64::INFINITY {
+                beta = f64::INFINITY;
+            } else {
+                break result;
+            }
+        };
+
+        // Check if time limit exceeded during search
+        if clock.expired() {
+            break;
+        }
+
+        if let Some(mv) = mv {
+            evaluation = eval;
+            best_move = Some(mv);
+            pv = PrincipalVariation { moves: pv_table.line(0) };
+            prev_score = Some(eval);
+            completed_depth = depth;
+            let stats = clock.stats();
+            depth_stats.depth_data.push(DepthData {
+                depth,
+                score: eval,
+                best_move: mv,
+                nodes: stats.total_nodes(),
+                time_ms: stats.elapsed.as_millis() as u64,
+                tt_hits: stats.tt_hits,
+            });
+            if let Some(callback) = search_callback {
+                callback(SearchInfo::new(depth, eval, &pv, clock));
+            }
+            // Not enough time left for a deeper iteration to finish
+            if depth >= min_depth && clock.soft_expired(config.soft_time_fraction) {
+                break;
+            }
+        } else {
+            // If no move was found (possibly due to timeout), break
+            break;
+        }
+    }
+
+    clock.set_ignore_time_limit(false);
+
+    let mut result = match (best_move, fallback) {
+        (None, Some((mv, score))) => SearchResult::new(Some(mv), score, PrincipalVariation { moves: vec![mv] }, 0),
+        _ => SearchResult::new(best_move, evaluation, pv, completed_depth),
+    };
+    result.depth_stats = depth_stats;
+    result
+}
+
+// Root moves scored by the static evaluation of the position they lead to,
+// best first
+fn static_move_scores(board: &BitBoard, player: i32, evaluator: &dyn Evaluator) -> Vec<WeightedMove> {
+    let mut scores: Vec<WeightedMove> = board
+        .get_valid_moves(player)
+        .into_iter()
+        .map(|mv| {
+            let mut new_board = *board;
+            new_board.make_move(&mv);
+            (mv, static_eval(&new_board, player, evaluator, false))
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scores
+}
+
+// Moves a game is expected to last per side, used when the number of moves
+// to the next time control is unknown
+const EXPECTED_GAME_MOVES: u32 = 40;
+// Always budget as if at least this many moves remain
+const MIN_MOVES_TO_GO: u32 = 10;
+
+// Splits the remaining clock time over the moves still to play
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    pub total_time_ms: u64, // Time left on the clock
+    pub increment_ms: u64, // Time added after each move
+    pub moves_to_go: Option<u32>, // Moves until the next time control, if any
+    pub overhead_ms: u64, // Reserved per move for communication delays
+}
+
+impl TimeManager {
+    // Spend exactly `time_limit` on every move
+    pub fn fixed(time_limit: Duration) -> Self {
+        TimeManager {
+            total_time_ms: time_limit.as_millis() as u64,
+            increment_ms: 0,
+            moves_to_go: Some(1),
+            overhead_ms: 0,
+        }
+    }
+
+    // Budget for the move at `ply` (plies played so far): an even share of
+    // the remaining time plus most of the increment, never more than is left
+    pub fn time_for_move(&self, ply: u32) -> Duration {
+        let moves_to_go = self
+            .moves_to_go
+            .unwrap_or_else(|| EXPECTED_GAME_MOVES.saturating_sub(ply / 2).max(MIN_MOVES_TO_GO))
+            .max(1);
+        let available = self.total_time_ms.saturating_sub(self.overhead_ms);
+        let budget = available / moves_to_go as u64 + self.increment_ms * 3 / 4;
+        Duration::from_millis(budget.min(available))
+    }
+}
+
+// Share of the time limit after which no new iteration is started
+pub const DEFAULT_SOFT_TIME_FRACTION: f64 = 0.5;
+
+// Depth iterative deepening always completes, however short the time limit
+pub const DEFAULT_MIN_DEPTH: i32 = 3;
+
+// Search options shared by `negamax` and `Engine`
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    pub max_depth: i32,
+    // Time per search for `negamax`; `Engine` budgets from a TimeManager
+    pub time_limit_secs: f64,
+    // No new iteration is started after this fraction of the time limit, as
+    // it would rarely finish; the full limit stops a search in progress
+    pub soft_time_fraction: f64,
+    // Iterations up to this depth (at most max_depth) run to completion even
+    // past the time limit
+    pub min_depth: i32,
+    pub null_move: Option<NullMoveConfig>,
+    pub lmr: Option<LmrConfig>,
+    // Prune late quiet moves at depth 1-3 when the static evaluation is at
+    // least this far below alpha
+    pub lmp_threshold: Option<f64>,
+    // Prune quiet moves at depth 1 when the static evaluation plus this
+    // margin cannot reach alpha
+    pub futility_margin: Option<f64>,
+    // Replace the search at depth 1-2 by a quiescence search when the static
+    // evaluation plus this margin cannot reach alpha, pruning if that fails low
+    pub razoring_margin: Option<f64>,
+    // Cut quiescence nodes whose stand-pat score plus this largest possible
+    // gain cannot reach alpha
+    pub delta_margin: Option<f64>,
+    pub aspiration_delta: Option<f64>,
+    pub use_history: bool,
+    pub use_counter_moves: bool,
+    pub use_gain_table: bool,
+    pub use_extensions: bool,
+    // Search positions with a single legal move one ply deeper
+    pub use_forced_capture_extension: bool,
+    // Search moves that leave the opponent a promotion one ply deeper
+    pub use_threat_extension: bool,
+    // Write every searched node to this file as newline-delimited JSON
+    pub log_tree: Option<PathBuf>,
+    // Search only this many root moves, the best by static evaluation
+    pub beam_width: Option<usize>,
+    pub parallel: bool,
+    pub tt_size_mb: usize,
+    pub threads: usize, // More than one enables Lazy SMP
+    // Share transposition entries between mirror-image positions. Only sound
+    // when the evaluation is symmetric as well.
+    pub use_symmetry: bool,
+    // Evict the least recently used transposition entries instead of
+    // replacing by depth
+    pub lru_tt: bool,
+}
+
+impl Default for SearchConfig {
+    // Plain alpha-beta with history ordering, stopped by the time limit
+    fn default() -> Self {
+        SearchConfig {
+            max_depth: DEFAULT_MAX_DEPTH,
+            time_limit_secs: 1.0,
+            soft_time_fraction: DEFAULT_SOFT_TIME_FRACTION,
+            min_depth: DEFAULT_MIN_DEPTH,
+            null_move: None,
+            lmr: None,
+            lmp_threshold: None,
+            futility_margin: None,
+            razoring_margin: None,
+            delta_margin: None,
+            aspiration_delta: None,
+            use_history: true,
+            use_counter_moves: false,
+            use_gain_table: false,
+            use_extensions: false,
+            use_forced_capture_extension: false,
+            use_threat_extension: false,
+            log_tree: None,
+            beam_width: None,
+            parallel: false,
+            tt_size_mb: DEFAULT_TT_SIZE_MB,
+            threads: 1,
+            use_symmetry: false,
+            lru_tt: false,
+        }
+    }
+}
+
+// Stateful engine that keeps its Zobrist keys and transposition table for a
+// whole game instead of rebuilding them on every search
+pub struct Engine {
+    zobrist_table: ZobristTable,
+    transposition_table: Box<dyn TranspositionStore>,
+    position_history: Vec<u64>,
+    half_move_clock: u32, // Plies since the last capture or forward move
+    weights: Weights,
+    evaluator: Arc<dyn Evaluator>, // Shared with the ponder thread
+    config: SearchConfig,
+    ponder: Option<Ponder>,
+    search_callback: Option<SearchCallback>, // Progress reports for `best_move`
+    last_stats: SearchStats,
+    last_pv: Vec<Move>, // Root line of the last `best_move` search's PV table
+    last_search_log: Option<Vec<SearchLogEntry>>, // Set when `config.log_tree` is
+    opening_book: Option<OpeningBook>, // Marks book moves in `annotate_move`
+    blunder_threshold: f64,
+    #[cfg(feature = "hash_stats")]
+    hash_stats: HashStats, // Over all `best_move` searches since the last reset
+}
+
+// Score loss against the best move above which `annotate_move` calls a move a
+// blunder; about two pieces with the default weights
+const DEFAULT_BLUNDER_THRESHOLD: f64 = 50.0;
+
+// Review of a played move. Scores are from the mover's point of view.
+#[derive(Debug, Clone)]
+pub struct MoveAnnotation {
+    pub mv: Move,
+    pub score: f64,
+    pub best_response: Option<Move>,
+    pub is_blunder: bool, // Scores more than `blunder_threshold` below the best move
+    pub blunder_threshold: f64,
+    pub is_book: bool,
+    pub pv_after: Vec<Move>, // Expected continuation, starting with `best_response`
+}
+
+// Background search on the position after an expected opponent reply. The
+// thread borrows the engine's transposition table and hands it back on join.
+struct Ponder {
+    expected_move: Move,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<(SearchResult, Box<dyn TranspositionStore>)>,
+}
+
+impl Engine {
+    pub fn new(weights: Weights, config: SearchConfig) -> Self {
+        Engine {
+            zobrist_table: ZobristTable::new_random(),
+            transposition_table: new_transposition_table(&config),
+            position_history: Vec::new(),
+            half_move_clock: 0,
+            evaluator: Arc::new(HandcraftedEvaluator { weights: weights.clone() }),
+            weights,
+            config,
+            ponder: None,
+            search_callback: None,
+            last_stats: SearchStats::default(),
+            last_pv: Vec::new(),
+            last_search_log: None,
+            opening_book: None,
+            blunder_threshold: DEFAULT_BLUNDER_THRESHOLD,
+            #[cfg(feature = "hash_stats")]
+            hash_stats: HashStats::default(),
+        }
+    }
+
+    // Play `mv` on `board` and record the resulting position. Returns the
+    // captured piece, or EMPTY.
+    pub fn make_move(&mut self, board: &mut Array2<i32>, mv: Move) -> i32 {
+        let player = board[[mv.from_row, mv.from_col]];
+        let mut hash = compute_zobrist_hash(board, &self.zobrist_table);
+        let captured_piece = make_move(board, &mv, player, &mut hash, &self.zobrist_table);
+        self.record_position(hash);
+        self.half_move_clock = next_half_move_clock(self.half_move_clock, &mv);
+        captured_piece
+    }
+
+    pub fn best_move(
+        &mut self,
+        board: &Array2<i32>,
+        player: i32,
+        time_manager: &TimeManager,
+    ) -> SearchResult {
+        self.finish_ponder();
+        let ply = self.position_history.len() as u32;
+        let clock = SearchClock::new(time_manager.time_for_move(ply), None).with_log(self.config.log_tree.as_deref());
+        let result = iterative_deepening(
+            board,
+            player,
+            self.evaluator.as_ref(),
+            &self.config,
+            &self.zobrist_table,
+            self.transposition_table.as_mut(),
+            &self.position_history,
+            self.half_move_clock,
+            &clock,
+            self.search_callback.as_deref(),
+        );
+        self.last_stats = clock.stats();
+        self.last_pv = result.pv.moves.clone();
+        #[cfg(feature = "hash_stats")]
+        self.hash_stats.add(&clock.hash_stats);
+        self.last_search_log = clock.into_log_entries();
+        result
+    }
+
+    // Score `mv` against every other move on `board` with a `depth` ply
+    // search, then search the reply
+    pub fn annotate_move(
+        &mut self,
+        board: &Array2<i32>,
+        player: i32,
+        mv: Move,
+        depth: i32,
+    ) -> Result<MoveAnnotation, IllegalMoveError> {
+        if !is_legal_move(board, mv, player) {
+            return Err(IllegalMoveError(mv));
+        }
+        self.finish_ponder();
+        let config = SearchConfig { max_depth: depth.max(1), ..self.config.clone() };
+        let scores = root_move_scores(board, player, self.evaluator.as_ref(), &config);
+        let best_score = scores.iter().map(|&(_, score)| score).fold(f64::NEG_INFINITY, f64::max);
+        let score = scores.iter().find(|&&(scored, _)| scored == mv).map_or(best_score, |&(_, score)| score);
+
+        let mut new_board = board.clone();
+        make_move(&mut new_board, &mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+        let reply_config = SearchConfig { max_depth: (depth - 1).max(1), ..config };
+        let clock = SearchClock::new(Duration::from_secs_f64(reply_config.time_limit_secs), None);
+        let reply = iterative_deepening(
+            &new_board,
+            -player,
+            self.evaluator.as_ref(),
+            &reply_config,
+            &self.zobrist_table,
+            self.transposition_table.as_mut(),
+            &self.position_history,
+            next_half_move_clock(self.half_move_clock, &mv),
+            &clock,
+            None,
+        );
+
+        let is_book = self
+            .opening_book
+            .as_ref()
+            .is_some_and(|book| book.move_counts(&compress_board(board)).iter().any(|&(book_move, _)| book_move == mv));
+        Ok(MoveAnnotation {
+            mv,
+            score,
+            best_response: reply.best_move,
+            is_blunder: best_score - score > self.blunder_threshold,
+            blunder_threshold: self.blunder_threshold,
+            is_book,
+            pv_after: reply.pv.moves,
+        })
+    }
+
+    // Book whose moves `annotate_move` marks; None removes it
+    pub fn set_opening_book(&mut self, opening_book: Option<OpeningBook>) {
+        self.opening_book = opening_book;
+    }
+
+    pub fn set_blunder_threshold(&mut self, blunder_threshold: f64) {
+        self.blunder_threshold = blunder_threshold;
+    }
+
+    // Nodes visited by the last `best_move` search, quiescence included
+    pub fn nodes_searched(&self) -> u64 {
+        self.last_stats.total_nodes()
+    }
+
+    // Share of transposition lookups in `best_move` searches since the last
+    // reset that found another position's entry under the same key
+    #[cfg(feature = "hash_stats")]
+    pub fn hash_collision_rate(&self) -> f64 {
+        self.hash_stats.collision_rate()
+    }
+
+    // Principal variation of the last `best_move` search, playable move by
+    // move from the searched position
+    pub fn get_pv(&self) -> Vec<Move> {
+        self.last_pv.clone()
+    }
+
+    // Search speed of the last `best_move` search
+    pub fn nps(&self) -> u64 {
+        self.last_stats.nps()
+    }
+
+    // Nodes logged by the last `best_move` search, up to the first
+    // MAX_SEARCH_LOG_ENTRIES; None unless `config.log_tree` is set
+    pub fn last_search_log(&self) -> Option<&[SearchLogEntry]> {
+        self.last_search_log.as_deref()
+    }
+
+    // Record a position reached outside `make_move`, e.g. the opponent's move
+    pub fn push_position(&mut self, board: &Array2<i32>) {
+        self.record_position(self.position_hash(board));
+    }
+
+    // Zobrist hash of `board` under this engine's keys, as recorded in the
+    // game history
+    pub fn position_hash(&self, board: &Array2<i32>) -> u64 {
+        compute_zobrist_hash(board, &self.zobrist_table)
+    }
+
+    // Add a position, by `position_hash`, to the game history that seeds
+    // repetition detection in every search
+    pub fn record_position(&mut self, hash: u64) {
+        self.position_history.push(hash);
+    }
+
+    // Times the position has occurred in the game so far
+    pub fn repetition_count(&self, hash: u64) -> usize {
+        self.position_history.iter().filter(|&&seen| seen == hash).count()
+    }
+
+    // True if the position has occurred more than once in the game
+    pub fn is_repetition(&self, hash: u64) -> bool {
+        self.repetition_count(hash) >= 2
+    }
+
+    // Set the plies since the last capture or forward move, for games
+    // recorded with `push_position`, which cannot tell what was played
+    pub fn set_half_move_clock(&mut self, half_move_clock: u32) {
+        self.half_move_clock = half_move_clock;
+    }
+
+    // True once the current position has occurred three times in the game or
+    // the fifty-move rule applies
+    pub fn claim_draw(&self) -> bool {
+        if self.half_move_clock >= HALF_MOVE_DRAW_LIMIT {
+            return true;
+        }
+        match self.position_history.last() {
+            Some(&current) => self.repetition_count(current) >= 3,
+            None => false,
+        }
+    }
+
+    // Report progress of `best_move` searches; pondering stays silent
+    pub fn set_search_callback(&mut self, search_callback: Option<SearchCallback>) {
+        self.search_callback = search_callback;
+    }
+
+    // Resize the transposition table to about `megabytes`, discarding its
+    // entries
+    pub fn set_tt_size(&mut self, megabytes: usize) {
+        self.finish_ponder();
+        self.config.tt_size_mb = megabytes;
+        self.transposition_table = new_transposition_table(&self.config);
+    }
+
+    // Search on `threads` threads with Lazy SMP; 1 disables it
+    pub fn set_threads(&mut self, threads: usize) {
+        self.config.threads = threads.max(1);
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.finish_ponder();
+        self.evaluator = Arc::from(evaluator);
+        // Stored scores came from the other evaluation
+        self.transposition_table.clear();
+    }
+
+    // Evaluate with `nn_eval` instead of the weights; None restores them
+    pub fn set_nn_eval(&mut self, nn_eval: Option<Arc<NnEval>>) {
+        self.finish_ponder();
+        self.evaluator = match nn_eval {
+            Some(nn_eval) => nn_eval,
+            None => Arc::new(HandcraftedEvaluator { weights: self.weights.clone() }),
+        };
+        self.transposition_table.clear();
+    }
+
+    // Think on the opponent's time: search the position after `our_move` (to
+    // be played on `board`) and `expected_opponent_move` in the background
+    // until `stop_ponder` is called or the search reaches max depth
+    pub fn start_ponder(&mut self, board: &Array2<i32>, our_move: Move, expected_opponent_move: Move) {
+        self.finish_ponder();
+
+        let player = board[[our_move.from_row, our_move.from_col]];
+        let mut ponder_board = board.clone();
+        let mut hash = compute_zobrist_hash(board, &self.zobrist_table);
+        let mut position_history = self.position_history.clone();
+        let mut half_move_clock = self.half_move_clock;
+        for mv in [our_move, expected_opponent_move] {
+            let piece = ponder_board[[mv.from_row, mv.from_col]];
+            make_move(&mut ponder_board, &mv, piece, &mut hash, &self.zobrist_table);
+            position_history.push(hash);
+            half_move_clock = next_half_move_clock(half_move_clock, &mv);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let clock = SearchClock::new(Duration::MAX, Some(Arc::clone(&stop)));
+        let mut transposition_table: Box<dyn TranspositionStore> =
+            std::mem::replace(&mut self.transposition_table, Box::new(TTable::new(0)));
+        let zobrist_table = self.zobrist_table;
+        let evaluator = Arc::clone(&self.evaluator);
+        let config = self.config.clone();
+
+        let handle = thread::spawn(move || {
+            let result = iterative_deepening(
+                &ponder_board,
+                player,
+                evaluator.as_ref(),
+                &config,
+                &zobrist_table,
+                transposition_table.as_mut(),
+                &position_history,
+                half_move_clock,
+                &clock,
+                None,
+            );
+            (result, transposition_table)
+        });
+
+        self.ponder = Some(Ponder {
+            expected_move: expected_opponent_move,
+            stop,
+            handle,
+        });
+    }
+
+    // Stop pondering once the opponent has played `opponent_move`. On a ponder
+    // hit returns the best reply found so far, otherwise None.
+    pub fn stop_ponder(&mut self, opponent_move: Move) -> Option<SearchResult> {
+        let expected_move = self.ponder.as_ref()?.expected_move;
+        let result = self.finish_ponder()?;
+        if opponent_move == expected_move && result.best_move.is_some() {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    // Stop the ponder thread, if any, and take back the transposition table.
+    // Nodes cut short by the stop are never stored, so the table only holds
+    // finished results. If the thread panicked its table is lost and the
+    // engine starts over with an empty one.
+    fn finish_ponder(&mut self) -> Option<SearchResult> {
+        let ponder = self.ponder.take()?;
+        ponder.stop.store(true, Ordering::Relaxed);
+        match ponder.handle.join() {
+            Ok((result, transposition_table)) => {
+                self.transposition_table = transposition_table;
+                Some(result)
+            }
+            Err(_) => {
+                self.transposition_table = new_transposition_table(&self.config);
+                None
+            }
+        }
+    }
+
+    // Forget everything learned during the current game
+    pub fn reset(&mut self) {
+        self.clear_history();
+        self.transposition_table.clear();
+        #[cfg(feature = "hash_stats")]
+        self.hash_stats.clear();
+    }
+
+    // Forget the positions played so far but keep the transposition table,
+    // e.g. before replaying a game up to a new position
+    pub fn clear_history(&mut self) {
+        self.finish_ponder();
+        self.position_history.clear();
+        self.half_move_clock = 0;
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.finish_ponder();
+    }
+}
+
+// Opening book mapping each known position, packed by `compress_board`, to
+// the moves played from it in the source games. A move appears once per game
+// that played it, so more common replies are listed more often.
+#[derive(Clone)]
+pub struct OpeningBook {
+    entries: HashMap<BookKey, Vec<Move>>,
+}
+
+type BookKey = [u8; COMPRESSED_BOARD_SIZE];
+
+impl Default for OpeningBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        OpeningBook { entries: HashMap::new() }
+    }
+
+    // Load every game in a PGN-style file. Header lines in brackets are
+    // skipped, move numbers ("1.") and results ("1-0", "*", ...) separate
+    // the moves, and each game is replayed from the starting position.
+    pub fn from_pgn_file(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut book = OpeningBook::new();
+        let mut game_moves = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                // A header after some movetext starts the next game
+                if !game_moves.is_empty() {
+                    book.add_game(&game_moves)?;
+                    game_moves.clear();
+                }
+                continue;
+            }
+
+            for token in line.split_whitespace() {
+                if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                    book.add_game(&game_moves)?;
+                    game_moves.clear();
+                    continue;
+                }
+                // Strip move numbers such as "1." or "1..."
+                let token = token.rsplit('.').next().unwrap_or("");
+                if token.is_empty() {
+                    continue;
+                }
+                let mv = parse_move(token)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+                game_moves.push(mv);
+            }
+        }
+
+        if !game_moves.is_empty() {
+            book.add_game(&game_moves)?;
+        }
+
+        Ok(book)
+    }
+
+    // Replay a game from the starting position, recording each move
+    fn add_game(&mut self, moves: &[Move]) -> io::Result<()> {
+        let mut board = starting_board();
+
+        for mv in moves {
+            let player = board[[mv.from_row, mv.from_col]];
+            if player == EMPTY || !get_valid_moves(&board, player).contains(mv) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("illegal move {} in book game", format_move(*mv)),
+                ));
+            }
+            self.entries.entry(compress_board(&board)).or_default().push(*mv);
+            make_move(&mut board, mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+        }
+
+        Ok(())
+    }
+
+    // Most frequently played book move for the position, if any
+    pub fn probe(&self, board: &Array2<i32>) -> Option<Move> {
+        // Earliest move wins ties
+        self.move_counts(&compress_board(board))
+            .into_iter()
+            .rev()
+            .max_by_key(|&(_, count)| count)
+            .map(|(mv, _)| mv)
+    }
+
+    // Distinct book moves for the position, in the order first played, with
+    // the number of times each was played
+    fn move_counts(&self, key: &BookKey) -> Vec<(Move, usize)> {
+        let mut counts: Vec<(Move, usize)> = Vec::new();
+        for &mv in self.entries.get(key).into_iter().flatten() {
+            match counts.iter_mut().find(|(book_move, _)| *book_move == mv) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((mv, 1)),
+            }
+        }
+        counts
+    }
+
+    // Random book move for the position, each as likely as it was played
+    pub fn sample(&self, board: &Array2<i32>, rng: &mut impl Rng) -> Option<Move> {
+        let moves = self.entries.get(&compress_board(board))?;
+        (!moves.is_empty()).then(|| moves[rng.gen_range(0..moves.len())])
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Write the book in a compact binary format. The crate does not depend
+    // on serde or bincode, so the layout is written by hand: the magic bytes,
+    // the entry count as a little-endian u64, then per entry the packed board,
+    // a u32 move count and four bytes per move.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        writer.write_all(OPENING_BOOK_MAGIC)?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for (key, moves) in &self.entries {
+            writer.write_all(key)?;
+            writer.write_all(&(moves.len() as u32).to_le_bytes())?;
+            for mv in moves {
+                writer.write_all(&[mv.from_row as u8, mv.from_col as u8, mv.to_row as u8, mv.to_col as u8])?;
+            }
+        }
+        writer.flush()
+    }
+
+    // Read a book written by `save`
+    pub fn load(path: &str) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let mut reader = data.as_slice();
+        let mut read_bytes = |n: usize| -> io::Result<&[u8]> {
+            if reader.len() < n {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated opening book"));
+            }
+            let (bytes, rest) = reader.split_at(n);
+            reader = rest;
+            Ok(bytes)
+        };
+
+        if read_bytes(OPENING_BOOK_MAGIC.len())? != OPENING_BOOK_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an opening book file"));
+        }
+        let mut book = OpeningBook::new();
+        let entry_count = u64::from_le_bytes(read_bytes(8)?.try_into().unwrap());
+        for _ in 0..entry_count {
+            let key: BookKey = read_bytes(COMPRESSED_BOARD_SIZE)?.try_into().unwrap();
+            let move_count = u32::from_le_bytes(read_bytes(4)?.try_into().unwrap());
+            let mut moves = Vec::with_capacity(move_count as usize);
+            for _ in 0..move_count {
+                let bytes = read_bytes(4)?;
+                if bytes.iter().any(|&x| x as usize >= BOARD_SIZE) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "book move off the board"));
+                }
+                moves.push(Move::new(bytes[0] as usize, bytes[1] as usize, bytes[2] as usize, bytes[3] as usize));
+            }
+            book.entries.insert(key, moves);
+        }
+        Ok(book)
+    }
+}
+
+// Identifies files written by `OpeningBook::save`
+const OPENING_BOOK_MAGIC: &[u8] = b"FIANCOBK1";
+
+// Book of the moves played in the first `max_ply` plies of `game_records`,
+// keeping only moves played at least `min_frequency` times from a position
+pub fn build_opening_book(game_records: &[GameResult], max_ply: usize, min_frequency: usize) -> OpeningBook {
+    let mut book = OpeningBook::new();
+    let mut counts: HashMap<(BookKey, Move), usize> = HashMap::new();
+    let mut first_seen = Vec::new(); // Keeps the book order deterministic
+
+    for record in game_records {
+        let mut board = starting_board();
+        let mut player = WHITE;
+        for mv in record.moves.iter().take(max_ply) {
+            // Records can be built by hand; the rest of a game after an
+            // illegal move is not read
+            if !is_legal_move(&board, *mv, player) {
+                break;
+            }
+            let count = counts.entry((compress_board(&board), *mv)).or_insert(0);
+            if *count == 0 {
+                first_seen.push((compress_board(&board), *mv));
+            }
+            *count += 1;
+            make_move(&mut board, mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+            player = -player;
+        }
+    }
+
+    for entry in first_seen {
+        let count = counts[&entry];
+        if count >= min_frequency {
+            let (key, mv) = entry;
+            book.entries.entry(key).or_default().extend(std::iter::repeat_n(mv, count));
+        }
+    }
+    book
+}
+
+// Every line of the book as a PGN-style game, most played moves first, for
+// reading by eye. Lines stop where the book has no move or a position
+// repeats.
+pub fn opening_book_to_pgn(book: &OpeningBook) -> String {
+    let mut lines = Vec::new();
+    collect_book_lines(book, &starting_board(), WHITE, &mut Vec::new(), &mut HashSet::new(), &mut lines);
+
+    let mut pgn = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        pgn.push_str(&format!("[Line \"{}\"]\n", index + 1));
+        for (ply, mv) in line.iter().enumerate() {
+            if ply % 2 == 0 {
+                pgn.push_str(&format!("{}. ", ply / 2 + 1));
+            }
+            pgn.push_str(&format_move(*mv));
+            pgn.push(' ');
+        }
+        pgn.push_str("*\n\n");
+    }
+    pgn
+}
+
+fn collect_book_lines(
+    book: &OpeningBook,
+    board: &Array2<i32>,
+    player: i32,
+    line: &mut Vec<Move>,
+    visited: &mut HashSet<BookKey>,
+    lines: &mut Vec<Vec<Move>>,
+) {
+    let key = compress_board(board);
+    let mut counts = book.move_counts(&key);
+    if counts.is_empty() || !visited.insert(key) {
+        if !line.is_empty() {
+            lines.push(line.clone());
+        }
+        return;
+    }
+    counts.sort_by_key(|&(_, count)| Reverse(count));
+
+    for (mv, _) in counts {
+        let mut new_board = board.clone();
+        make_move(&mut new_board, &mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+        line.push(mv);
+        collect_book_lines(book, &new_board, -player, line, visited, lines);
+        line.pop();
+    }
+    visited.remove(&key);
+}
+
+// Square names use file letters a-i for columns and ranks 1-9 counted from
+// White's side, so row 8 is rank 1 and row 0 is rank 9
+fn parse_square(s: &str) -> Option<(usize, usize)> {
+    let mut chars = s.chars();
+    let file = chars.next()?;
+    let rank: usize = chars.as_str().parse().ok()?;
+    if !('a'..='i').contains(&file) || !(1..=BOARD_SIZE).contains(&rank) {
+        return None;
+    }
+    Some((BOARD_SIZE - rank, file as usize - 'a' as usize))
+}
+
+// Inverse of `parse_square`
+pub(crate) fn square_name(row: usize, col: usize) -> String {
+    format!("{}{}", (b'a' + col as u8) as char, BOARD_SIZE - row)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseMoveError {
+    MissingSeparator(String),
+    InvalidSquare(String),
+}
+
+impl std::fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseMoveError::MissingSeparator(s) => write!(f, "invalid move '{}': expected two squares joined by '-'", s),
+            ParseMoveError::InvalidSquare(s) => write!(f, "invalid square '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+// Moves are two squares joined by '-', each either a square name ("e2-e3")
+// or a (row, col) pair ("(7,4)-(6,4)")
+pub fn parse_move(s: &str) -> Result<Move, ParseMoveError> {
+    let (from, to) = s
+        .split_once('-')
+        .ok_or_else(|| ParseMoveError::MissingSeparator(s.to_string()))?;
+    let (from_row, from_col) = parse_move_square(from)?;
+    let (to_row, to_col) = parse_move_square(to)?;
+    Ok(Move::new(from_row, from_col, to_row, to_col))
+}
+
+fn parse_move_square(s: &str) -> Result<(usize, usize), ParseMoveError> {
+    let s = s.trim();
+    let square = match s.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+        Some(coordinates) => coordinates.split_once(',').and_then(|(row, col)| {
+            let row: usize = row.trim().parse().ok()?;
+            let col: usize = col.trim().parse().ok()?;
+            (row < BOARD_SIZE && col < BOARD_SIZE).then_some((row, col))
+        }),
+        None => parse_square(s),
+    };
+    square.ok_or_else(|| ParseMoveError::InvalidSquare(s.to_string()))
+}
+
+// Canonical move notation, e.g. "e2-e3"
+pub fn format_move(mv: Move) -> String {
+    format!(
+        "{}-{}",
+        square_name(mv.from_row, mv.from_col),
+        square_name(mv.to_row, mv.to_col)
+    )
+}
+
+// Standard Fianco setup: each side has its back row plus a V of six pieces
+pub fn starting_board() -> Array2<i32> {
+    let mut board = Array2::zeros((BOARD_SIZE, BOARD_SIZE));
+    for col in 0..BOARD_SIZE {
+        board[[0, col]] = BLACK;
+        board[[BOARD_SIZE - 1, col]] = WHITE;
+    }
+    for i in 1..4 {
+        board[[i, i]] = BLACK;
+        board[[i, BOARD_SIZE - 1 - i]] = BLACK;
+        board[[BOARD_SIZE - 1 - i, i]] = WHITE;
+        board[[BOARD_SIZE - 1 - i, BOARD_SIZE - 1 - i]] = WHITE;
+    }
+    board
+}
+
+pub fn is_starting_position(board: &Array2<i32>) -> bool {
+    *board == starting_board()
+}
+
+// Pieces each side has in the starting position
+const PIECES_PER_SIDE: usize = 15;
+
+// Reasons a board cannot occur in a game of Fianco
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    WrongDimensions { rows: usize, cols: usize },
+    InvalidPieceValue(i32),
+    TooManyPieces { player: i32, count: usize },
+    // A piece no starting piece can have reached, given pieces never move
+    // back towards their own side
+    InvalidStartingPiece { row: usize, col: usize },
+}
+
+impl std::fmt::Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardError::WrongDimensions { rows, cols } => {
+                write!(f, "board is {}x{}, expected {}x{}", rows, cols, BOARD_SIZE, BOARD_SIZE)
+            }
+            BoardError::InvalidPieceValue(value) => write!(f, "invalid piece value {}", value),
+            BoardError::TooManyPieces { player, count } => {
+                write!(f, "player {} has {} pieces, at most {} allowed", player, count, PIECES_PER_SIDE)
+            }
+            BoardError::InvalidStartingPiece { row, col } => {
+                write!(f, "piece at ({}, {}) cannot come from the starting position", row, col)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+pub(crate) fn check_dimensions(board: &Array2<i32>) -> Result<(), BoardError> {
+    let (rows, cols) = board.dim();
+    if (rows, cols) != (BOARD_SIZE, BOARD_SIZE) {
+        return Err(BoardError::WrongDimensions { rows, cols });
+    }
+    Ok(())
+}
+
+// Checks that `board` could arise from the starting position
+pub fn validate_board(board: &Array2<i32>) -> Result<(), BoardError> {
+    check_dimensions(board)?;
+    if let Some(&value) = board.iter().find(|&&piece| ![EMPTY, BLACK, WHITE].contains(&piece)) {
+        return Err(BoardError::InvalidPieceValue(value));
+    }
+
+    let start = starting_board();
+    for player in [BLACK, WHITE] {
+        let count = board.iter().filter(|&&piece| piece == player).count();
+        if count > PIECES_PER_SIDE {
+            return Err(BoardError::TooManyPieces { player, count });
+        }
+
+        // Walking away from the player's own side, no more pieces can be on
+        // or behind a row than started there
+        let rows: Vec<usize> = if player == BLACK {
+            (0..BOARD_SIZE).collect()
+        } else {
+            (0..BOARD_SIZE).rev().collect()
+        };
+        let (mut on_board, mut at_start) = (0, 0);
+        for row in rows {
+            at_start += start.row(row).iter().filter(|&&piece| piece == player).count();
+            for col in 0..BOARD_SIZE {
+                if board[[row, col]] == player {
+                    on_board += 1;
+                    if on_board > at_start {
+                        return Err(BoardError::InvalidStartingPiece { row, col });
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// The move that turned one board into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardDiff {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub captured: Option<(usize, usize)>, // Square of the jumped piece
+    pub piece: i32, // Player whose piece moved
+}
+
+impl BoardDiff {
+    pub fn to_move(&self) -> Move {
+        Move::new(self.from.0, self.from.1, self.to.0, self.to.1)
+    }
+}
+
+// Reasons two boards are not one move apart
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardDiffError {
+    Board(BoardError),
+    NoChange,
+    // The squares that differ, row by row
+    NotASingleMove(Vec<(usize, usize)>),
+}
+
+impl std::fmt::Display for BoardDiffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardDiffError::Board(err) => write!(f, "{}", err),
+            BoardDiffError::NoChange => write!(f, "the boards are identical"),
+            BoardDiffError::NotASingleMove(squares) => {
+                let names: Vec<String> = squares.iter().map(|&(row, col)| square_name(row, col)).collect();
+                write!(f, "changes on {} are not a single move", names.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoardDiffError {}
+
+// Reconstructs the move played between `before` and `after`: one piece
+// leaves its square for an empty one, possibly removing the opponent piece
+// it jumped. Any other set of changes is reported with the squares involved.
+pub fn board_diff(before: &Array2<i32>, after: &Array2<i32>) -> Result<BoardDiff, BoardDiffError> {
+    check_dimensions(before).map_err(BoardDiffError::Board)?;
+    check_dimensions(after).map_err(BoardDiffError::Board)?;
+
+    let changed: Vec<(usize, usize)> = (0..BOARD_SIZE)
+        .flat_map(|row| (0..BOARD_SIZE).map(move |col| (row, col)))
+        .filter(|&(row, col)| before[[row, col]] != after[[row, col]])
+        .collect();
+    if changed.is_empty() {
+        return Err(BoardDiffError::NoChange);
+    }
+    let not_a_move = || BoardDiffError::NotASingleMove(changed.clone());
+
+    // Exactly one square gains a piece, and it was empty before
+    let mut filled = changed.iter().filter(|&&(row, col)| after[[row, col]] != EMPTY);
+    let (Some(&to), None) = (filled.next(), filled.next()) else {
+        return Err(not_a_move());
+    };
+    let piece = after[[to.0, to.1]];
+    if before[[to.0, to.1]] != EMPTY {
+        return Err(not_a_move());
+    }
+
+    // Every other change empties a square: the moved piece's and at most
+    // one opponent piece halfway along the jump
+    let (own, jumped): (Vec<Square>, Vec<Square>) = changed
+        .iter()
+        .filter(|&&square| square != to)
+        .partition(|&&(row, col)| before[[row, col]] == piece);
+    let (&[from], &[] | &[_]) = (own.as_slice(), jumped.as_slice()) else {
+        return Err(not_a_move());
+    };
+    let captured = jumped.first().copied();
+    let midpoint = ((from.0 + to.0) / 2, (from.1 + to.1) / 2);
+    let valid_capture = captured.is_none_or(|square| {
+        before[[square.0, square.1]] == -piece && from.0.abs_diff(to.0) == 2 && square == midpoint
+    });
+    if !valid_capture {
+        return Err(not_a_move());
+    }
+    Ok(BoardDiff { from, to, captured, piece })
+}
+
+// Board notation: one string per row from row 0 to row 8, joined by '/',
+// with 'B' for black, 'W' for white and '.' for empty, followed by a space
+// and the player to move ('B' or 'W')
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    RowCount(usize),
+    RowLength { row: usize, length: usize },
+    InvalidPiece(char),
+    InvalidPlayer(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::RowCount(count) => {
+                write!(f, "expected {} rows, found {}", BOARD_SIZE, count)
+            }
+            FenError::RowLength { row, length } => {
+                write!(f, "row {} has {} squares, expected {}", row, length, BOARD_SIZE)
+            }
+            FenError::InvalidPiece(c) => write!(f, "invalid piece '{}'", c),
+            FenError::InvalidPlayer(s) => write!(f, "invalid player to move '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+// Parses a board and the player to move
+pub(crate) fn parse_fen(fen: &str) -> Result<(Array2<i32>, i32), FenError> {
+    let mut parts = fen.split_whitespace();
+    let rows: Vec<&str> = parts.next().unwrap_or("").split('/').collect();
+    let player = match parts.next() {
+        Some("B") => BLACK,
+        Some("W") => WHITE,
+        other => return Err(FenError::InvalidPlayer(other.unwrap_or("").to_string())),
+    };
+
+    if rows.len() != BOARD_SIZE {
+        return Err(FenError::RowCount(rows.len()));
+    }
+
+    let mut board = Array2::zeros((BOARD_SIZE, BOARD_SIZE));
+    for (row, squares) in rows.iter().enumerate() {
+        let length = squares.chars().count();
+        if length != BOARD_SIZE {
+            return Err(FenError::RowLength { row, length });
+        }
+        for (col, c) in squares.chars().enumerate() {
+            board[[row, col]] = match c {
+                'B' => BLACK,
+                'W' => WHITE,
+                '.' => EMPTY,
+                _ => return Err(FenError::InvalidPiece(c)),
+            };
+        }
+    }
+
+    Ok((board, player))
+}
+
+pub fn board_from_fen(fen: &str) -> Result<Array2<i32>, FenError> {
+    parse_fen(fen).map(|(board, _)| board)
+}
+
+pub fn board_to_fen(board: &Array2<i32>, player: i32) -> String {
+    let rows: Vec<String> = board
+        .rows()
+        .into_iter()
+        .map(|row| {
+            row.iter()
+                .map(|&piece| match piece {
+                    BLACK => 'B',
+                    WHITE => 'W',
+                    _ => '.',
+                })
+                .collect()
+        })
+        .collect();
+    let active = if player == BLACK { 'B' } else { 'W' };
+    format!("{} {}", rows.join("/"), active)
+}
+
+// Plies (50 moves each) without a capture or forward move after which the
+// game is drawn
+const HALF_MOVE_DRAW_LIMIT: u32 = 100;
+
+// Half-move clock after `mv`. Captures and forward moves, the only ones that
+// change row, can never be undone over the board and reset it.
+fn next_half_move_clock(half_move_clock: u32, mv: &Move) -> u32 {
+    if mv.from_row != mv.to_row {
+        0
+    } else {
+        half_move_clock + 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllegalMoveError(pub Move);
+
+impl std::fmt::Display for IllegalMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "illegal move {}", format_move(self.0))
+    }
+}
+
+impl std::error::Error for IllegalMoveError {}
+
+// A game in progress: the board, the player to move and everything needed to
+// take moves back and detect draws
+#[derive(Clone)]
+pub struct GameState {
+    pub board: Array2<i32>,
+    pub player: i32,
+    pub move_history: Vec<Move>,
+    pub position_hashes: Vec<u64>, // Includes the starting position
+    pub half_move_clock: u32, // Plies since the last capture or forward move
+    undo_stack: Vec<(i32, u32)>, // Captured piece and previous clock per move
+    zobrist_table: ZobristTable,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for GameState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", format_board(&self.board))
+    }
+}
+
+impl std::fmt::Debug for GameState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let player = if self.player == BLACK { "black" } else { "white" };
+        write!(f, "{}", format_board(&self.board))?;
+        writeln!(f, "to move: {}, half-move clock: {}", player, self.half_move_clock)?;
+        write!(f, "fen: {}", self.to_fen())
+    }
+}
+
+// The board as text, rank 9 at the top, with ranks and files labelled as in
+// move notation: B for black, W for white, . for empty
+pub(crate) fn format_board(board: &Array2<i32>) -> String {
+    let files: Vec<String> = (0..BOARD_SIZE).map(|col| ((b'a' + col as u8) as char).to_string()).collect();
+    let mut text = format!("   {}\n", files.join(" "));
+    for row in 0..BOARD_SIZE {
+        let squares: Vec<&str> = (0..BOARD_SIZE)
+            .map(|col| match board[[row, col]] {
+                BLACK => "B",
+                WHITE => "W",
+                _ => ".",
+            })
+            .collect();
+        text.push_str(&format!("{}  {}\n", BOARD_SIZE - row, squares.join(" ")));
+    }
+    text.push_str(&format!("   {}\n", "-".repeat(2 * BOARD_SIZE - 1)));
+    text
+}
+
+impl GameState {
+    // Standard starting position, White to move
+    pub fn new() -> Self {
+        Self::from_board(starting_board(), WHITE)
+    }
+
+    pub fn from_board(board: Array2<i32>, player: i32) -> Self {
+        let zobrist_table = ZobristTable::new_random();
+        let hash = compute_zobrist_hash(&board, &zobrist_table);
+        GameState {
+            board,
+            player,
+            move_history: Vec::new(),
+            position_hashes: vec![hash],
+            half_move_clock: 0,
+            undo_stack: Vec::new(),
+            zobrist_table,
+        }
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let (board, player) = parse_fen(fen)?;
+        Ok(GameState::from_board(board, player))
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if get_winner(&self.board).is_some() {
+            return Vec::new();
+        }
+        get_valid_moves(&self.board, self.player)
+    }
+
+    // Play `mv` for the player to move. Returns the captured piece, or EMPTY.
+    pub fn apply_move(&mut self, mv: Move) -> Result<i32, IllegalMoveError> {
+        if !self.legal_moves().contains(&mv) {
+            return Err(IllegalMoveError(mv));
+        }
+
+        let mut hash = *self.position_hashes.last().unwrap();
+        let captured_piece = make_move(&mut self.board, &mv, self.player, &mut hash, &self.zobrist_table);
+
+        self.undo_stack.push((captured_piece, self.half_move_clock));
+        self.half_move_clock = next_half_move_clock(self.half_move_clock, &mv);
+        self.move_history.push(mv);
+        self.position_hashes.push(hash);
+        self.player = -self.player;
+        Ok(captured_piece)
+    }
+
+    // Take back the last move, returning it
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let mv = self.move_history.pop()?;
+        let (captured_piece, half_move_clock) = self.undo_stack.pop()?;
+        self.position_hashes.pop();
+        self.player = -self.player;
+        self.half_move_clock = half_move_clock;
+
+        self.board[[mv.from_row, mv.from_col]] = self.player;
+        self.board[[mv.to_row, mv.to_col]] = EMPTY;
+        if captured_piece != EMPTY {
+            self.board[[(mv.from_row + mv.to_row) / 2, (mv.from_col + mv.to_col) / 2]] = captured_piece;
+        }
+        Some(mv)
+    }
+
+    pub fn winner(&self) -> Option<i32> {
+        get_winner(&self.board).or_else(|| self.legal_moves().is_empty().then_some(-self.player))
+    }
+
+    // True after 50 moves by each side without a capture or forward move
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.half_move_clock >= HALF_MOVE_DRAW_LIMIT
+    }
+
+    // Threefold repetition or too long without progress
+    pub fn is_draw(&self) -> bool {
+        if self.is_fifty_move_draw() {
+            return true;
+        }
+        match self.position_hashes.last() {
+            Some(current) => self.position_hashes.iter().filter(|&hash| hash == current).count() >= 3,
+            None => false,
+        }
+    }
+
+    pub fn to_fen(&self) -> String {
+        board_to_fen(&self.board, self.player)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn negamax_search(
+    board: &BitBoard,
+    depth: i32,
+    player: i32,
+    mut alpha: f64,
+    mut beta: f64,
+    evaluator: &dyn Evaluator,
+    with_mobility: bool, // Full evaluation rather than `evaluate_fast`
+    zobrist_hash: u64,
+    zobrist_table: &ZobristTable,
+    transposition_table: &mut dyn TranspositionStore,
+    use_symmetry: bool,
+    zobrist_stack: &mut ZobristStack,
+    clock: &SearchClock,
+    first_move: Option<Move>, // Best move from previous iteration
+    null_move: Option<&NullMoveConfig>,
+    lmr: Option<&LmrConfig>,
+    lmp_threshold: Option<f64>, // Late move pruning margin below alpha
+    futility_margin: Option<f64>, // Futility pruning margin at depth 1
+    razoring_margin: Option<f64>, // Razoring margin at depth 1-2
+    delta_margin: Option<f64>, // Delta pruning margin in quiescence search
+    killers: &mut KillerTable,
+    pv_table: &mut PvTable,
+    mut history: Option<&mut HistoryTable>,
+    mut counter_moves: Option<&mut CounterMoveTable>,
+    mut gain_table: Option<&mut GainTable>,
+    last_move: Option<Move>, // Opponent move that led to this position
+    ply: usize, // Distance from the root
+    half_move_clock: u32, // Plies since the last capture or forward move
+    in_null_move: bool, // True if the parent node passed the turn
+    use_extensions: bool, // Search forcing positions one ply deeper
+    use_forced_capture_extension: bool, // Search single-move positions one ply deeper
+    forced_extensions: &mut u32, // Forced capture extensions on the path so far
+    use_threat_extension: bool, // Search moves allowing a promotion one ply deeper
+    threat_extensions: &mut u32, // Threat extensions on the path so far
+) -> (f64, Option<Move>) {
+    debug_assert!(hash_in_sync(board, zobrist_hash, zobrist_table), "incremental Zobrist hash out of sync");
+    clock.count_node();
+    pv_table.clear(ply);
+
+    // Check if time limit exceeded
+    if clock.expired() {
+        return (0.0, None); // Return default value on timeout
+    }
+
+    // Draw by the fifty-move rule
+    if half_move_clock >= HALF_MOVE_DRAW_LIMIT && ply > 0 {
+        return (0.0, None);
+    }
+
+    // Threefold repetition detection, counting only the positions on the
+    // path to this node. The root is never scored as a draw so a move is
+    // always returned.
+    zobrist_stack.push(zobrist_hash);
+    if ply > 0 && zobrist_stack.count_occurrences(zobrist_hash) >= 3 {
+        zobrist_stack.pop();
+        return (0.0, None);
+    }
+
+    let tt_key = TTKey::new(board, zobrist_hash, zobrist_table, use_symmetry);
+
+    // Transposition Table Lookup
+    let tt_entry = transposition_table.probe(tt_key.hash);
+    #[cfg(feature = "hash_stats")]
+    clock.hash_stats.record_lookup(tt_entry.is_some_and(|entry| entry.position != tt_key.position));
+    // Nodes searched with more than a null window can be on the principal
+    // variation. An exact score inside their window would end the PV at this
+    // node, as its PV table row stays empty, so such nodes are searched.
+    let pv_node = beta - alpha > NULL_WINDOW;
+    if let Some(entry) = tt_entry {
+        clock.count_tt_hit();
+        let entry = tt_key.orient(entry);
+        if entry.depth >= depth {
+            match entry.flag {
+                NodeType::Exact if pv_node && alpha < entry.value && entry.value < beta => {}
+                NodeType::Exact => {
+                    // Leave the search path before returning
+                    zobrist_stack.pop();
+                    return (entry.value, entry.best_move);
+                },
+                NodeType::LowerBound => alpha = alpha.max(entry.value),
+                NodeType::UpperBound => beta = beta.min(entry.value),
+            }
+            if alpha >= beta {
+                // Leave the search path before returning
+                zobrist_stack.pop();
+                return (entry.value, entry.best_move);
+            }
+        }
+    }
+
+    // Terminal Node Check
+    if depth <= 0 || board.get_winner().is_some() {
+        // Resolve pending captures at the horizon before evaluating
+        let mut evaluation = quiescence_search(
+            board,
+            player,
+            alpha,
+            beta,
+            evaluator,
+            with_mobility,
+            zobrist_hash,
+            zobrist_table,
+            clock,
+            delta_margin,
+        );
+        let penalty = evaluator.repetition_penalty();
+        if penalty != 0.0 {
+            evaluation += repetition_penalty(zobrist_hash, zobrist_stack.history(), evaluation > 0.0, penalty);
+        }
+        clock.log_node(|| SearchLogEntry {
+            depth,
+            mv: last_move,
+            alpha,
+            beta,
+            eval: evaluation,
+            cutoff: evaluation >= beta,
+        });
+        // Leave the search path before returning
+        zobrist_stack.pop();
+        return (evaluation, None);
+    }
+
+    let alpha_orig = alpha;
+
+    // Generate Valid Moves
+    let moves = board.get_valid_moves(player);
+
+    if moves.is_empty() {
+        // No moves available, losing position
+        // Leave the search path before returning
+        zobrist_stack.pop();
+        return (LOSE_SCORE, None);
+    }
+
+    // Extensions: search forcing positions a ply deeper, as long as the
+    // line stays within the killer table
+    let extension = (use_extensions
+        && ply + depth as usize + 1 < MAX_DEPTH
+        && is_forcing_position(&board.to_array2(), player)) as i32;
+
+    // Forced capture extension: a lone legal move, which is a capture
+    // whenever one exists, is searched a ply deeper, up to a cap per path
+    let forced_extension = (use_forced_capture_extension
+        && extension == 0
+        && moves.len() == 1
+        && *forced_extensions < MAX_FORCED_EXTENSIONS
+        && ply + depth as usize + 1 < MAX_DEPTH) as i32;
+    let extension = extension + forced_extension;
+    *forced_extensions += forced_extension as u32;
+
+    // Null-Move Pruning: pass the turn and search at reduced depth. If the
+    // opponent still cannot bring the score below beta, the node is pruned.
+    // Skipped right after another null move, when captures are forced and
+    // when we have few pieces left (zugzwang-prone).
+    if let Some(config) = null_move {
+        if !in_null_move
+            && depth > config.reduction
+            && !is_capture_move(board, &moves[0], player)
+            && board.count_pieces(player) >= config.min_pieces
+        {
+            let null_hash = zobrist_hash ^ NULL_MOVE_HASH_KEY;
+            let (null_eval, _) = negamax_search(
+                board,
+                depth - config.reduction - 1,
+                -player,
+                -beta,
+                -beta + NULL_WINDOW,
+                evaluator,
+                with_mobility,
+                null_hash,
+                zobrist_table,
+                transposition_table,
+                use_symmetry,
+                zobrist_stack,
+                clock,
+                None,
+                null_move,
+                lmr,
+                lmp_threshold,
+                futility_margin,
+                razoring_margin,
+                delta_margin,
+                killers,
+                pv_table,
+                history.as_deref_mut(),
+                counter_moves.as_deref_mut(),
+                gain_table.as_deref_mut(),
+                None,
+                ply + 1,
+                half_move_clock,
+                true,
+                use_extensions,
+                use_forced_capture_extension,
+                forced_extensions,
+                use_threat_extension,
+                threat_extensions,
+            );
+            let null_eval = -null_eval;
+
+            if null_eval >= beta && !clock.expired() {
+                *forced_extensions -= forced_extension as u32;
+                // Leave the search path before returning
+                zobrist_stack.pop();
+                return (beta, None);
+            }
+        }
+    }
+
+    // Razoring: a shallow node far below alpha only gets a quiescence search,
+    // and is pruned if that cannot reach alpha either
+    if let Some(margin) = razoring_margin.filter(|_| ply > 0 && depth <= 2) {
+        let static_eval = static_eval(board, player, evaluator, with_mobility);
+        if razoring_applicable(depth, static_eval, alpha, margin) {
+            let q_eval = quiescence_search(
+                board,
+                player,
+                alpha,
+                beta,
+                evaluator,
+                with_mobility,
+                zobrist_hash,
+                zobrist_table,
+                clock,
+                delta_margin,
+            );
+            if q_eval <= alpha {
+                *forced_extensions -= forced_extension as u32;
+                // Leave the search path before returning
+                zobrist_stack.pop();
+                return (q_eval, None);
+            }
+        }
+    }
+
+    // Move Ordering: previous best move, TT move, killers, the counter move,
+    // captures by MVV-LVA, then quiet moves by history score
+    let tt_move = transposition_table
+        .probe(tt_key.hash)
+        .and_then(|entry| tt_key.orient(entry).best_move);
+    let ply_killers = killers.get(ply).copied().unwrap_or([None; 2]);
+    let counter_move = last_move
+        .zip(counter_moves.as_deref())
+        .and_then(|(last_move, counter_moves)| counter_moves.get(&last_move).copied());
+    let mut ordered_moves = moves;
+    ordered_moves.sort_by_cached_key(|mv| {
+        let gain = gain_table
+            .as_deref()
+            .filter(|_| !is_capture_move(board, mv, player))
+            .map_or(0, |gain_table| (gain_table.gain(mv) * ORDER_GAIN_SCALE) as i64);
+        Reverse((
+            score_move_for_ordering(
+                board,
+                mv,
+                player,
+                first_move,
+                tt_move,
+                &ply_killers,
+                counter_move,
+                history.as_deref(),
+            ),
+            gain,
+        ))
+    });
+
+    let mut max_eval = LOSE_SCORE;
+    let mut best_move = None;
+
+    // Evaluation before the move, for measuring the gain of quiet moves
+    let parent_eval = gain_table.is_some().then(|| static_eval(board, player, evaluator, false));
+
+    // Static evaluation for the shallow-depth pruning below, away from the root
+    let static_eval = (ply > 0
+        && ((lmp_threshold.is_some() && depth < LMP_MOVE_COUNTS.len() as i32)
+            || (futility_margin.is_some() && depth == 1)))
+        .then(|| static_eval(board, player, evaluator, with_mobility));
+
+    // Search through ordered moves
+    for (move_index, mv) in ordered_moves.into_iter().enumerate() {
+        // Check if time limit exceeded
+        if clock.expired() {
+            break;
+        }
+
+        if let Some(static_eval) = static_eval.filter(|_| !is_capture_move(board, &mv, player)) {
+            // Futility Pruning: count the move as the optimistic bound
+            if let Some(margin) = futility_margin {
+                if depth == 1 && futility_prunable(static_eval, alpha, margin) {
+                    max_eval = max_eval.max(static_eval + margin);
+                    continue;
+                }
+            }
+
+            // Late Move Pruning: skip hopeless quiet moves at shallow depth
+            if lmp_threshold.is_some_and(|threshold| lmp_ok(depth, move_index, static_eval, alpha, threshold)) {
+                continue;
+            }
+        }
+
+        let mut new_board = *board;
+        let mut new_hash = zobrist_hash;
+
+        let captured_piece = new_board.make_move(&mv);
+        update_zobrist_hash(&mut new_hash, &mv, player, captured_piece, zobrist_table);
+
+        // Threat extension: look a ply deeper when the move leaves the
+        // opponent a promotion, up to a cap per path
+        let threat_extension = (use_threat_extension
+            && extension == 0
+            && *threat_extensions < MAX_THREAT_EXTENSIONS
+            && ply + depth as usize + 1 < MAX_DEPTH
+            && opponent_can_promote_next(&new_board.to_array2(), player)) as i32;
+        *threat_extensions += threat_extension as u32;
+
+        if let (Some(gain_table), Some(parent_eval)) = (gain_table.as_deref_mut(), parent_eval) {
+            if captured_piece == EMPTY {
+                gain_table.record(mv, evaluation_gain(parent_eval, &new_board, player, evaluator));
+            }
+        }
+
+        // Late Move Reductions: quiet moves late in the ordering are searched
+        // at reduced depth first
+        let reduction = match lmr {
+            Some(config)
+                if depth >= 3
+                    && move_index >= config.full_depth_moves
+                    && !is_capture_move(board, &mv, player) =>
+            {
+                lmr_reduction(depth, move_index, config.reduction_limit)
+            }
+            _ => 0,
+        };
+
+        let (mut eval, _) = negamax_search(
+            &new_board,
+            depth - 1 - reduction + extension + threat_extension,
+            -player,
+            -beta,
+            -alpha,
+            evaluator,
+            with_mobility,
+            new_hash,
+            zobrist_table,
+            transposition_table,
+            use_symmetry,
+            zobrist_stack,
+            clock,
+            None, // No specific move ordering in deeper levels
+            null_move,
+            lmr,
+            lmp_threshold,
+            futility_margin,
+            razoring_margin,
+            delta_margin,
+            killers,
+            pv_table,
+            history.as_deref_mut(),
+            counter_moves.as_deref_mut(),
+            gain_table.as_deref_mut(),
+            Some(mv),
+            ply + 1,
+            next_half_move_clock(half_move_clock, &mv),
+            false,
+            use_extensions,
+            use_forced_capture_extension,
+            forced_extensions,
+            use_threat_extension,
+            threat_extensions,
+        );
+        eval = -eval;
+
+        // Re-search at full depth if the reduced search beats alpha
+        if reduction > 0 && eval > alpha {
+            let (full_eval, _) = negamax_search(
+                &new_board,
+                depth - 1 + extension + threat_extension,
+                -player,
+                -beta,
+                -alpha,
+                evaluator,
+                with_mobility,
+                new_hash,
+                zobrist_table,
+                transposition_table,
+                use_symmetry,
+                zobrist_stack,
+                clock,
+                None,
+                null_move,
+                lmr,
+                lmp_threshold,
+                futility_margin,
+                razoring_margin,
+                delta_margin,
+                killers,
+                pv_table,
+                history.as_deref_mut(),
+                counter_moves.as_deref_mut(),
+                gain_table.as_deref_mut(),
+                Some(mv),
+                ply + 1,
+                next_half_move_clock(half_move_clock, &mv),
+                false,
+                use_extensions,
+                use_forced_capture_extension,
+                forced_extensions,
+                use_threat_extension,
+                threat_extensions,
+            );
+            eval = -full_eval;
+        }
+        *threat_extensions -= threat_extension as u32;
+
+        // A child cut short by the time limit has no meaningful score
+        if clock.expired() {
+            break;
+        }
+
+        if eval > max_eval {
+            max_eval = eval;
+            best_move = Some(mv);
+            pv_table.update(ply, mv);
+        }
+
+        alpha = alpha.max(eval);
+        if alpha >= beta {
+            // Remember quiet moves that cause a cutoff as killers and in
+            // the history table
+            if !is_capture_move(board, &mv, player) {
+                if ply < MAX_DEPTH {
+                    store_killer(&mut killers[ply], mv);
+                }
+                if let Some(history) = history.as_deref_mut() {
+                    *history.entry(mv).or_insert(0) += (depth * depth) as i64;
+                }
+                if let (Some(counter_moves), Some(last_move)) = (counter_moves.as_deref_mut(), last_move) {
+                    counter_moves.insert(last_move, mv);
+                }
+            }
+            break;
+        }
+    }
+
+    *forced_extensions -= forced_extension as u32;
+
+    // Store in Transposition Table, unless the search was cut short and the
+    // score is unreliable
+    if !clock.expired() {
+        let flag = if max_eval <= alpha_orig {
+            NodeType::UpperBound
+        } else if max_eval >= beta {
+            NodeType::LowerBound
+        } else {
+            NodeType::Exact
+        };
+
+        let entry = TTEntry {
+            key: tt_key.hash,
+            #[cfg(feature = "hash_stats")]
+            position: tt_key.position,
+            depth,
+            value: max_eval,
+            flag,
+            best_move,
+        };
+
+        transposition_table.store(tt_key.hash, tt_key.orient(entry));
+    }
+
+    clock.log_node(|| SearchLogEntry {
+        depth,
+        mv: last_move,
+        alpha: alpha_orig,
+        beta,
+        eval: max_eval,
+        cutoff: max_eval >= beta,
+    });
+
+    // Leave the search path before returning
+    zobrist_stack.pop();
+
+    (max_eval, best_move)
+}
+
+
+
+// Root search that splits the root moves across Rayon worker threads. The
+// previous best move is searched first on the calling thread to establish a
+// bound; the remaining moves then run in parallel, each with its own board,
+// transposition table and ordering tables, sharing the best score so far
+// through an atomic. Thread-local tables are merged back afterwards.
+#[allow(clippy::too_many_arguments)]
+fn parallel_root_search(
+    board: &BitBoard,
+    depth: i32,
+    player: i32,
+    alpha: f64,
+    beta: f64,
+    evaluator: &dyn Evaluator,
+    with_mobility: bool,
+    zobrist_hash: u64,
+    zobrist_table: &ZobristTable,
+    transposition_table: &mut dyn TranspositionStore,
+    use_symmetry: bool,
+    zobrist_stack: &ZobristStack,
+    clock: &SearchClock,
+    first_move: Option<Move>,
+    null_move: Option<&NullMoveConfig>,
+    lmr: Option<&LmrConfig>,
+    lmp_threshold: Option<f64>, // Late move pruning margin below alpha
+    futility_margin: Option<f64>, // Futility pruning margin at depth 1
+    razoring_margin: Option<f64>, // Razoring margin at depth 1-2
+    delta_margin: Option<f64>, // Delta pruning margin in quiescence search
+    killers: &mut KillerTable,
+    pv_table: &mut PvTable,
+    history: Option<&mut HistoryTable>,
+    counter_moves: Option<&mut CounterMoveTable>,
+    gain_table: Option<&mut GainTable>,
+    half_move_clock: u32,
+    use_extensions: bool,
+    use_forced_capture_extension: bool,
+    use_threat_extension: bool,
+) -> (f64, Option<Move>) {
+    clock.count_node();
+    pv_table.clear(0);
+    let mut moves = board.get_valid_moves(player);
+    if moves.is_empty() {
+        return (LOSE_SCORE, None);
+    }
+
+    // Search the previous best move first
+    if let Some(index) = moves.iter().position(|&mv| Some(mv) == first_move) {
+        moves[..=index].rotate_right(1);
+    }
+
+    let use_history = history.is_some();
+    let use_counter_moves = counter_moves.is_some();
+    let use_gain_table = gain_table.is_some();
+    let best_score = AtomicU64::new(alpha.to_bits());
+
+    let search_root_move = |mv: Move,
+                            transposition_table: &mut dyn TranspositionStore,
+                            killers: &mut KillerTable,
+                            pv_table: &mut PvTable,
+                            history: Option<&mut HistoryTable>,
+                            counter_moves: Option<&mut CounterMoveTable>,
+                            gain_table: Option<&mut GainTable>| {
+        let mut new_board = *board;
+        let mut new_hash = zobrist_hash;
+        let captured_piece = new_board.make_move(&mv);
+        update_zobrist_hash(&mut new_hash, &mv, player, captured_piece, zobrist_table);
+
+        // Count the root as visited, as the serial search does on entry
+        let mut zobrist_stack = zobrist_stack.clone();
+        zobrist_stack.push(zobrist_hash);
+
+        let alpha = f64::from_bits(best_score.load(Ordering::Relaxed));
+        let (eval, _) = negamax_search(
+            &new_board,
+            depth - 1,
+            -player,
+            -beta,
+            -alpha,
+            evaluator,
+            with_mobility,
+            new_hash,
+            zobrist_table,
+            transposition_table,
+            use_symmetry,
+            &mut zobrist_stack,
+            clock,
+            None,
+            null_move,
+            lmr,
+            lmp_threshold,
+            futility_margin,
+            razoring_margin,
+            delta_margin,
+            killers,
+            pv_table,
+            history,
+            counter_moves,
+            gain_table,
+            Some(mv),
+            1,
+            next_half_move_clock(half_move_clock, &mv),
+            false,
+            use_extensions,
+            use_forced_capture_extension,
+            &mut 0,
+            use_threat_extension,
+            &mut 0,
+        );
+        let eval = -eval;
+        atomic_max_f64(&best_score, eval);
+
+        let mut pv = vec![mv];
+        pv.extend(pv_table.line(1));
+        (eval, mv, pv)
+    };
+
+    let mut results =
+        vec![search_root_move(moves[0], transposition_table, killers, pv_table, history, counter_moves, gain_table)];
+
+    let parallel_results: Vec<_> = moves[1..]
+        .par_iter()
+        .map(|&mv| {
+            let mut local_table = TTable::new(PARALLEL_TT_SIZE_MB);
+            let mut local_pv_table = PvTable::new();
+            let mut local_killers = [[None; 2]; MAX_DEPTH];
+            let mut local_history = HashMap::new();
+            let mut local_counter_moves = HashMap::new();
+            let mut local_gain_table = GainTable::new();
+            let result = search_root_move(
+                mv,
+                &mut local_table,
+                &mut local_killers,
+                &mut local_pv_table,
+                if use_history { Some(&mut local_history) } else { None },
+                if use_counter_moves { Some(&mut local_counter_moves) } else { None },
+                if use_gain_table { Some(&mut local_gain_table) } else { None },
+            );
+            (result, local_table)
+        })
+        .collect();
+
+    // Merge thread-local transposition tables into the shared one
+    for (result, local_table) in parallel_results {
+        for entry in local_table.entries() {
+            transposition_table.store(entry.key, *entry);
+        }
+        results.push(result);
+    }
+
+    // Pick the best root move, preferring earlier moves on ties
+    let mut max_eval = LOSE_SCORE;
+    let mut best_move = None;
+    let mut pv_line = Vec::new();
+    for (eval, mv, pv) in results {
+        if best_move.is_none() || eval > max_eval {
+            max_eval = eval;
+            best_move = Some(mv);
+            pv_line = pv;
+        }
+    }
+
+    let flag = if max_eval <= alpha {
+        NodeType::UpperBound
+    } else if max_eval >= beta {
+        NodeType::LowerBound
+    } else {
+        NodeType::Exact
+    };
+    let tt_key = TTKey::new(board, zobrist_hash, zobrist_table, use_symmetry);
+    let entry = TTEntry {
+        key: tt_key.hash,
+        #[cfg(feature = "hash_stats")]
+        position: tt_key.position,
+        depth,
+        value: max_eval,
+        flag,
+        best_move,
+    };
+    if !clock.expired() {
+        transposition_table.store(tt_key.hash, tt_key.orient(entry));
+    }
+
+    clock.log_node(|| SearchLogEntry { depth, mv: None, alpha, beta, eval: max_eval, cutoff: max_eval >= beta });
+
+    pv_table.set_line(0, &pv_line);
+    (max_eval, best_move)
+}
+
+// Raise an f64 stored as bits in an AtomicU64 to at least `value`
+fn atomic_max_f64(target: &AtomicU64, value: f64) {
+    let mut current = target.load(Ordering::Relaxed);
+    while f64::from_bits(current) < value {
+        match target.compare_exchange_weak(current, value.to_bits(), Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => break,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+// Quiescence search: keep playing captures past the horizon until the
+// position is quiet, using the static evaluation as a stand-pat lower bound
+#[allow(clippy::too_many_arguments)]
+fn quiescence_search(
+    board: &BitBoard,
+    player: i32,
+    mut alpha: f64,
+    beta: f64,
+    evaluator: &dyn Evaluator,
+    with_mobility: bool,
+    zobrist_hash: u64,
+    zobrist_table: &ZobristTable,
+    clock: &SearchClock,
+    delta_margin: Option<f64>,
+) -> f64 {
+    debug_assert!(hash_in_sync(board, zobrist_hash, zobrist_table), "incremental Zobrist hash out of sync");
+
+    let stand_pat = static_eval(board, player, evaluator, with_mobility);
+
+    if board.get_winner().is_some() || stand_pat >= beta || clock.expired() {
+        return stand_pat;
+    }
+    if delta_margin.is_some_and(|max_gain| delta_prune(stand_pat, alpha, max_gain)) {
+        return stand_pat;
+    }
+    alpha = alpha.max(stand_pat);
+
+    let capture_moves = board.get_capture_moves(player);
+    let mut best_eval = stand_pat;
+
+    for mv in capture_moves {
+        let mut new_board = *board;
+        let mut new_hash = zobrist_hash;
+
+        let captured_piece = new_board.make_move(&mv);
+        update_zobrist_hash(&mut new_hash, &mv, player, captured_piece, zobrist_table);
+        // Horizon nodes are already counted by `negamax_search`
+        clock.count_q_node();
+
+        let eval = -quiescence_search(
+            &new_board,
+            -player,
+            -beta,
+            -alpha,
+            evaluator,
+            with_mobility,
+            new_hash,
+            zobrist_table,
+            clock,
+            delta_margin,
+        );
+
+        best_eval = best_eval.max(eval);
+        alpha = alpha.max(eval);
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    best_eval
+}
+
+// Hashes of the positions on the path from the start of the game to the node
+// being searched. Positions reached on sibling paths have been popped again,
+// so only true repetitions are counted.
+#[derive(Debug, Clone, Default)]
+pub struct ZobristStack(Vec<u64>);
+
+impl ZobristStack {
+    pub fn new() -> Self {
+        ZobristStack(Vec::new())
+    }
+
+    pub fn push(&mut self, hash: u64) {
+        self.0.push(hash);
+    }
+
+    pub fn pop(&mut self) -> Option<u64> {
+        self.0.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn count_occurrences(&self, hash: u64) -> usize {
+        self.0.iter().filter(|&&seen| seen == hash).count()
+    }
+
+    // The positions before the top of the stack
+    pub fn history(&self) -> &[u64] {
+        &self.0[..self.0.len().saturating_sub(1)]
+    }
+}
+
+// Fill `zobrist_stack` with the positions played so far in the game,
+// including the current position at least once
+fn seed_zobrist_stack(zobrist_stack: &mut ZobristStack, position_history: &[u64], current_hash: u64) {
+    zobrist_stack.clear();
+    for &hash in position_history {
+        zobrist_stack.push(hash);
+    }
+    if !position_history.contains(&current_hash) {
+        zobrist_stack.push(current_hash);
+    }
+}
+
+// Average change in static evaluation caused by each quiet move, gathered
+// over the iterations of one search and used to order quiet moves with equal
+// history scores
+#[derive(Debug, Default)]
+pub struct GainTable {
+    gains: HashMap<Move, f64>,
+    counts: HashMap<Move, u32>,
+}
+
+impl GainTable {
+    pub fn new() -> Self {
+        GainTable::default()
+    }
+
+    pub fn record(&mut self, mv: Move, gain: f64) {
+        let count = self.counts.entry(mv).or_insert(0);
+        *count += 1;
+        let average = self.gains.entry(mv).or_insert(0.0);
+        *average += (gain - *average) / *count as f64;
+    }
+
+    // Average gain of `mv`, 0 if it has not been played
+    pub fn gain(&self, mv: &Move) -> f64 {
+        self.gains.get(mv).copied().unwrap_or(0.0)
+    }
+}
+
+// Change in `player`'s static evaluation from `parent_eval` to the position
+// after their move
+fn evaluation_gain(parent_eval: f64, new_board: &BitBoard, player: i32, evaluator: &dyn Evaluator) -> f64 {
+    -static_eval(new_board, -player, evaluator, false) - parent_eval
+}
+
+fn store_killer(slots: &mut [Option<Move>; 2], mv: Move) {
+    if slots[0] != Some(mv) {
+        slots[1] = slots[0];
+        slots[0] = Some(mv);
+    }
+}
+
+// Futility pruning: a quiet move at depth 1 is not worth searching when even
+// `margin` on top of the static evaluation would not reach alpha
+fn futility_prunable(static_eval: f64, alpha: f64, margin: f64) -> bool {
+    static_eval + margin <= alpha
+}
+
+// Razoring: at depth 1-2 a node whose static evaluation is `margin` or more
+// below alpha is not expected to recover without captures
+fn razoring_applicable(depth: i32, static_eval: f64, alpha: f64, margin: f64) -> bool {
+    (1..=2).contains(&depth) && static_eval + margin <= alpha
+}
+
+// Delta pruning: no capture from a quiescence node is worth searching when
+// even `max_gain` on top of the stand-pat score would not reach alpha
+fn delta_prune(stand_pat: f64, alpha: f64, max_gain: f64) -> bool {
+    stand_pat + max_gain < alpha
+}
+
+// Quiet moves searched before late move pruning starts, indexed by depth
+const LMP_MOVE_COUNTS: [usize; 4] = [0, 4, 7, 12];
+
+// Whether late move pruning may skip the quiet move at `move_index`: only at
+// depth 1-3, late in the ordering and with the static evaluation at least
+// `threshold` below alpha
+fn lmp_ok(depth: i32, move_index: usize, static_eval: f64, alpha: f64, threshold: f64) -> bool {
+    (1..LMP_MOVE_COUNTS.len() as i32).contains(&depth)
+        && move_index >= LMP_MOVE_COUNTS[depth as usize]
+        && static_eval + threshold <= alpha
+}
+
+// Depth reduction for a late quiet move, growing with both depth and move index
+fn lmr_reduction(depth: i32, move_index: usize, reduction_limit: i32) -> i32 {
+    let reduction = ((depth as f64).ln() * (move_index as f64).ln() / 2.0).round() as i32;
+    reduction.max(1).min(reduction_limit).min(depth - 1)
+}
+
+// Mirror the board left to right. The rules are symmetric under this flip,
+// so a position and its mirror image have the same value.
+pub fn flip_board(board: &Array2<i32>) -> Array2<i32> {
+    board.slice(s![.., ..;-1]).to_owned()
+}
+
+// The same position with the sides swapped: rows reversed and every piece
+// changing colour, so Black's pieces become White's as seen from White's side
+pub fn flip_player(board: &Array2<i32>) -> Array2<i32> {
+    board.slice(s![..;-1, ..]).mapv(|piece| -piece)
+}
+
+// Panics unless `evaluate_board` scores `board` the same for Black as it
+// scores the colour-flipped board for White, and as the negation of White's
+// score of `board` itself. Finished games are skipped: boards where both
+// sides have reached the last row cannot occur but get one winner.
+#[cfg(debug_assertions)]
+pub fn assert_evaluation_symmetry(board: &Array2<i32>, weights: &Weights) {
+    if get_winner(board).is_some() {
+        return;
+    }
+    let flipped = flip_player(board);
+    for with_mobility in [false, true] {
+        let black = evaluate_board(board, BLACK, weights, with_mobility);
+        let white = evaluate_board(board, WHITE, weights, with_mobility);
+        let flipped_white = evaluate_board(&flipped, WHITE, weights, with_mobility);
+        let tolerance = 1e-9 * black.abs().max(1.0);
+        assert!(
+            (black - flipped_white).abs() <= tolerance && (black + white).abs() <= tolerance,
+            "asymmetric evaluation of {}: {} for Black, {} for White, {} for White after flipping sides",
+            board_to_fen(board, BLACK),
+            black,
+            white,
+            flipped_white,
+        );
+    }
+}
+
+// Hash shared by a position and its mirror image. Only for transposition
+// lookups: repetitions must still use `compute_zobrist_hash`.
+pub fn canonical_zobrist_hash(board: &Array2<i32>, zobrist_table: &ZobristTable) -> u64 {
+    compute_zobrist_hash(board, zobrist_table).min(compute_zobrist_hash(&flip_board(board), zobrist_table))
+}
+
+// Bytes needed for two bits per square
+pub const COMPRESSED_BOARD_SIZE: usize = (BOARD_SIZE * BOARD_SIZE * 2).div_ceil(8);
+
+// Pack a board into two bits per square, row by row from the low bits of
+// the first byte: 0 for empty, 1 for Black and 2 for White
+pub fn compress_board(board: &Array2<i32>) -> [u8; COMPRESSED_BOARD_SIZE] {
+    let mut data = [0u8; COMPRESSED_BOARD_SIZE];
+    for (square, &piece) in board.iter().enumerate() {
+        let code = match piece {
+            BLACK => 1,
+            WHITE => 2,
+            _ => 0,
+        };
+        data[square / 4] |= code << (2 * (square % 4));
+    }
+    data
+}
+
+// Inverse of `compress_board`
+pub fn decompress_board(data: &[u8; COMPRESSED_BOARD_SIZE]) -> Array2<i32> {
+    Array2::from_shape_fn((BOARD_SIZE, BOARD_SIZE), |(row, col)| {
+        let square = row * BOARD_SIZE + col;
+        match (data[square / 4] >> (2 * (square % 4))) & 3 {
+            1 => BLACK,
+            2 => WHITE,
+            _ => EMPTY,
+        }
+    })
+}
+
+// Input planes for a network: friendly pieces, opponent pieces and a plane
+// of ones when Black is to move, each BOARD_SIZE x BOARD_SIZE
+pub fn position_planes(board: &Array2<i32>, player: i32) -> Array3<f32> {
+    let to_move = if player == BLACK { 1.0 } else { 0.0 };
+    Array3::from_shape_fn((3, BOARD_SIZE, BOARD_SIZE), |(plane, row, col)| match plane {
+        0 => (board[[row, col]] == player) as u8 as f32,
+        1 => (board[[row, col]] == -player) as u8 as f32,
+        _ => to_move,
+    })
+}
+
+// `position_planes` for each position, stacked along a leading batch axis
+pub fn batch_positions_to_tensor(boards: &[(Array2<i32>, i32)]) -> Array4<f32> {
+    let mut batch = Array4::zeros((boards.len(), 3, BOARD_SIZE, BOARD_SIZE));
+    for (mut planes, (board, player)) in batch.outer_iter_mut().zip(boards) {
+        planes.assign(&position_planes(board, *player));
+    }
+    batch
+}
+
+// Full static evaluation of each (board, player) pair, on the Rayon pool
+pub fn batch_evaluate(positions: &[(Array2<i32>, i32)], weights: &Weights) -> Vec<f64> {
+    positions
+        .par_iter()
+        .map(|(board, player)| evaluate_board(board, *player, weights, true))
+        .collect()
+}
+
+// Zobrist keys, indexed by row, column and `piece_index`
+#[derive(Debug, Clone, Copy)]
+pub struct ZobristTable([[[u64; 3]; BOARD_SIZE]; BOARD_SIZE]);
+
+impl ZobristTable {
+    // Keys differ on every call, so hashes are only comparable within a table
+    pub fn new_random() -> Self {
+        Self::from_rng(&mut rand::thread_rng())
+    }
+
+    // The same keys for the same seed, for reproducible hashes
+    pub fn new_seeded(seed: u64) -> Self {
+        Self::from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng(rng: &mut impl Rng) -> Self {
+        let mut keys = [[[0u64; 3]; BOARD_SIZE]; BOARD_SIZE];
+        for row in keys.iter_mut() {
+            for square in row.iter_mut() {
+                for key in square.iter_mut() {
+                    *key = rng.gen();
+                }
+            }
+        }
+        ZobristTable(keys)
+    }
+
+    pub fn compute_hash(&self, board: &Array2<i32>) -> u64 {
+        compute_zobrist_hash(board, self)
+    }
+}
+
+impl std::ops::Index<usize> for ZobristTable {
+    type Output = [[u64; 3]; BOARD_SIZE];
+
+    fn index(&self, row: usize) -> &Self::Output {
+        &self.0[row]
+    }
+}
+
+// All-zero keys, for `make_move` calls that keep no hash
+pub(crate) const NO_ZOBRIST_KEYS: ZobristTable = ZobristTable([[[0; 3]; BOARD_SIZE]; BOARD_SIZE]);
+
+fn piece_index(piece: i32) -> usize {
+    match piece {
+        BLACK => 1,
+        WHITE => 2,
+        _ => 0, // EMPTY
+    }
+}
+
+fn compute_zobrist_hash(board: &Array2<i32>, zobrist_table: &ZobristTable) -> u64 {
+    let mut hash: u64 = 0;
+    for ((row, col), &piece) in board.indexed_iter() {
+        let piece_idx = piece_index(piece);
+        if piece_idx != 0 {
+            hash ^= zobrist_table[row][col][piece_idx];
+        }
+    }
+    hash
+}
+
+
+fn is_capture_move(_board: &BitBoard, mv: &Move, _player: i32) -> bool {
+    let Move { from_row, to_row, .. } = *mv;
+    let delta_row = (to_row as isize - from_row as isize).abs();
+    delta_row == 2 // Capture moves involve jumping over an opponent's piece
+}
+
+// Ordering score bands, so that each kind of move sorts ahead of the next
+// whatever the score within its band
+const ORDER_FIRST_MOVE: i64 = 1 << 50;
+const ORDER_TT_MOVE: i64 = 1 << 49;
+const ORDER_KILLER: i64 = 1 << 48;
+const ORDER_COUNTER_MOVE: i64 = 1 << 47;
+const ORDER_CAPTURE: i64 = 1 << 46;
+// Gains are scaled by this before rounding to an ordering score
+const ORDER_GAIN_SCALE: f64 = 100.0;
+// Weight of the exchange result within the capture band, above any
+// `score_capture` value
+const ORDER_SEE_SCALE: i32 = 1000;
+
+// Single ordering score for a move; moves are searched highest first. Quiet
+// moves score their history value, if history is in use.
+#[allow(clippy::too_many_arguments)]
+fn score_move_for_ordering(
+    board: &BitBoard,
+    mv: &Move,
+    player: i32,
+    first_move: Option<Move>, // Best move from the previous iteration
+    tt_move: Option<Move>,
+    killers: &[Option<Move>; 2],
+    counter_move: Option<Move>, // Refutation of the opponent's last move
+    history: Option<&HistoryTable>,
+) -> i64 {
+    if first_move == Some(*mv) {
+        return ORDER_FIRST_MOVE;
+    }
+    if tt_move == Some(*mv) {
+        return ORDER_TT_MOVE;
+    }
+    if let Some(slot) = killers.iter().position(|&killer| killer == Some(*mv)) {
+        return ORDER_KILLER - slot as i64;
+    }
+    if counter_move == Some(*mv) {
+        return ORDER_COUNTER_MOVE;
+    }
+    if is_capture_move(board, mv, player) {
+        // Captures that keep the material come before those that lose it back
+        return ORDER_CAPTURE + (see(board, mv, player) * ORDER_SEE_SCALE + score_capture(board, mv, player)) as i64;
+    }
+    history.and_then(|history| history.get(mv).copied()).unwrap_or(0)
+}
+
+// MVV-LVA style capture score. All pieces have the same material value, so a
+// piece is worth how far it has advanced: taking an advanced enemy piece
+// scores highest, with the attacker's advancement after the jump as tiebreak.
+fn score_capture(_board: &BitBoard, mv: &Move, player: i32) -> i32 {
+    let Move { from_row, to_row, .. } = *mv;
+    let victim_row = (from_row + to_row) / 2;
+    let victim_value = advancement(victim_row, -player);
+    let attacker_value = advancement(to_row, player);
+    victim_value * 10 - attacker_value
+}
+
+// Static exchange evaluation: the pieces `player` comes out ahead after the
+// capture `mv` and the chain of recaptures of each capturing piece, both
+// sides picking their best recapture. All pieces being equal, this is 1 when
+// the chain ends with `player`'s capture and 0 when the piece is won back.
+pub(crate) fn see(board: &BitBoard, mv: &Move, player: i32) -> i32 {
+    let mut new_board = *board;
+    new_board.make_move(mv);
+    1 - recapture_gain(&new_board, (mv.to_row, mv.to_col), -player)
+}
+
+// Best net material for `player` from capturing the piece on `square`, with
+// the recaptures that follow; 0 if it cannot be captured
+fn recapture_gain(board: &BitBoard, square: (usize, usize), player: i32) -> i32 {
+    board
+        .get_capture_moves(player)
+        .iter()
+        .filter(|mv| ((mv.from_row + mv.to_row) / 2, (mv.from_col + mv.to_col) / 2) == square)
+        .map(|mv| {
+            let mut new_board = *board;
+            new_board.make_move(mv);
+            1 - recapture_gain(&new_board, (mv.to_row, mv.to_col), -player)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+// Number of rows a piece on `row` has advanced from its own back rank
+fn advancement(row: usize, player: i32) -> i32 {
+    if player == BLACK {
+        row as i32
+    } else {
+        (BOARD_SIZE - 1 - row) as i32
+    }
+}
+
+pub(crate) fn make_move(
+    board: &mut Array2<i32>,
+    mv: &Move,
+    _player: i32,
+    zobrist_hash: &mut u64,
+    zobrist_table: &ZobristTable,
+) -> i32 {
+    let Move { from_row, from_col, to_row, to_col } = *mv;
+
+    let from_piece = board[[from_row, from_col]];
+    let _to_piece = board[[to_row, to_col]]; // Should be EMPTY
+
+    // Remove piece from old position
+    *zobrist_hash ^= zobrist_table[from_row][from_col][piece_index(from_piece)];
+    // Place piece at new position
+    *zobrist_hash ^= zobrist_table[to_row][to_col][piece_index(from_piece)];
+
+    // Update the board
+    board[[to_row, to_col]] = from_piece;
+    board[[from_row, from_col]] = EMPTY;
+
+    let mut captured_piece = EMPTY;
+
+    // Check if it's a capture
+    if (from_row as isize - to_row as isize).abs() == 2 {
+        let mid_row = (from_row + to_row) / 2;
+        let mid_col = (from_col + to_col) / 2;
+        captured_piece = board[[mid_row, mid_col]];
+        // Remove captured piece
+        *zobrist_hash ^= zobrist_table[mid_row][mid_col][piece_index(captured_piece)];
+        board[[mid_row, mid_col]] = EMPTY;
+    }
+
+    captured_piece
+}
+
+// Reverse `make_move`, given the piece it returned as captured
+fn undo_move(
+    board: &mut Array2<i32>,
+    mv: &Move,
+    captured_piece: i32,
+    zobrist_hash: &mut u64,
+    zobrist_table: &ZobristTable,
+) {
+    let Move { from_row, from_col, to_row, to_col } = *mv;
+
+    let piece = board[[to_row, to_col]];
+    *zobrist_hash ^= zobrist_table[to_row][to_col][piece_index(piece)];
+    *zobrist_hash ^= zobrist_table[from_row][from_col][piece_index(piece)];
+    board[[from_row, from_col]] = piece;
+    board[[to_row, to_col]] = EMPTY;
+
+    if captured_piece != EMPTY {
+        let mid_row = (from_row + to_row) / 2;
+        let mid_col = (from_col + to_col) / 2;
+        *zobrist_hash ^= zobrist_table[mid_row][mid_col][piece_index(captured_piece)];
+        board[[mid_row, mid_col]] = captured_piece;
+    }
+}
+
+// Static evaluation of a position for `player`, used at the leaves of the
+// search. Finished games never reach it; the search scores those itself.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, board: &Array2<i32>, player: i32) -> f64;
+
+    // Cheaper variant for shallow iterations, by default the full evaluation
+    fn evaluate_fast(&self, board: &Array2<i32>, player: i32) -> f64 {
+        self.evaluate(board, player)
+    }
+
+    // Penalty the search applies to repeated positions, none by default
+    fn repetition_penalty(&self) -> f64 {
+        0.0
+    }
+}
+
+// The weighted hand-crafted features of `evaluate_board`
+pub struct HandcraftedEvaluator {
+    pub weights: Weights,
+}
+
+impl Evaluator for HandcraftedEvaluator {
+    fn evaluate(&self, board: &Array2<i32>, player: i32) -> f64 {
+        evaluate_board(board, player, &self.weights, true)
+    }
+
+    // Skips the mobility term
+    fn evaluate_fast(&self, board: &Array2<i32>, player: i32) -> f64 {
+        evaluate_board(board, player, &self.weights, false)
+    }
+
+    fn repetition_penalty(&self) -> f64 {
+        self.weights.repetition_penalty
+    }
+}
+
+// Score adjustment for a position that has occurred before: the side that is
+// winning loses `penalty` for repeating, the side that is losing gains it, so
+// a won game is not drawn by repetition and a lost one may be
+pub fn repetition_penalty(board_hash: u64, position_history: &[u64], player_is_winning: bool, penalty: f64) -> f64 {
+    if !position_history.contains(&board_hash) {
+        0.0
+    } else if player_is_winning {
+        -penalty
+    } else {
+        penalty
+    }
+}
+
+// Uniformly random scores, for testing move ordering in isolation
+pub struct RandomEvaluator;
+
+impl Evaluator for RandomEvaluator {
+    fn evaluate(&self, _board: &Array2<i32>, _player: i32) -> f64 {
+        rand::thread_rng().gen_range(-RANDOM_EVAL_RANGE..RANDOM_EVAL_RANGE)
+    }
+}
+
+// Scores of `RandomEvaluator` fall within plus or minus this
+const RANDOM_EVAL_RANGE: f64 = 100.0;
+
+// Piece count difference only, for fast testing
+pub struct MaterialOnlyEvaluator {
+    pub piece_value: f64,
+}
+
+impl Evaluator for MaterialOnlyEvaluator {
+    fn evaluate(&self, board: &Array2<i32>, player: i32) -> f64 {
+        self.piece_value * board.iter().map(|&piece| piece * player).sum::<i32>() as f64
+    }
+}
+
+// Evaluation of a search node, with finished games scored as wins or losses
+fn static_eval(board: &BitBoard, player: i32, evaluator: &dyn Evaluator, with_mobility: bool) -> f64 {
+    match board.get_winner() {
+        Some(winner) if winner == player => WIN_SCORE,
+        Some(_) => LOSE_SCORE,
+        None if with_mobility => evaluator.evaluate(&board.to_array2(), player),
+        None => evaluator.evaluate_fast(&board.to_array2(), player),
+    }
+}
+
+// `with_mobility` enables the mobility term, which costs two extra move
+// generations per call
+pub(crate) fn evaluate_board(board: &Array2<i32>, player: i32, weights: &Weights, with_mobility: bool) -> f64 {
+    // Check for game over
+    if let Some(winner) = get_winner(board) {
+        if winner == player {
+            return WIN_SCORE;
+        } else {
+            return LOSE_SCORE;
+        }
+    }
+
+    if is_endgame(board) {
+        return evaluate_endgame(board, player, weights);
+    }
+    evaluate_features(board, player, weights, with_mobility)
+}
+
+// Both sides are down to this many pieces or fewer in the endgame
+const ENDGAME_MAX_PIECES: usize = 5;
+// Endgame weights relative to the middlegame ones: the race to the last row
+// counts for more, room to move for less
+const ENDGAME_ADVANCEMENT_SCALE: f64 = 2.0;
+const ENDGAME_MOBILITY_SCALE: f64 = 0.5;
+
+pub fn is_endgame(board: &Array2<i32>) -> bool {
+    let (black_count, white_count) = count_pieces(board);
+    black_count <= ENDGAME_MAX_PIECES && white_count <= ENDGAME_MAX_PIECES
+}
+
+// Evaluation used by `evaluate_board` in the endgame: the features under
+// `Weights::endgame`, less a piece when `player` is in zugzwang and plus one
+// when the opponent is
+pub fn evaluate_endgame(board: &Array2<i32>, player: i32, weights: &Weights) -> f64 {
+    let mut score = evaluate_features(board, player, &weights.endgame(), true);
+    if in_zugzwang(board, player) {
+        score -= weights.piece_value;
+    }
+    if in_zugzwang(board, -player) {
+        score += weights.piece_value;
+    }
+    score
+}
+
+// True if `player` has moves but no captures, and every move hands the
+// opponent a capture
+fn in_zugzwang(board: &Array2<i32>, player: i32) -> bool {
+    let moves = get_valid_moves(board, player);
+    !moves.is_empty()
+        && !has_capture(board, player)
+        && moves.iter().all(|mv| {
+            let mut new_board = board.clone();
+            make_move(&mut new_board, mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+            has_capture(&new_board, -player)
+        })
+}
+
+// The weighted evaluation terms of a position that is not yet decided
+fn evaluate_features(board: &Array2<i32>, player: i32, weights: &Weights, with_mobility: bool) -> f64 {
+    let mut score = 0.0;
+
+    // Iterate over the board and calculate features. A piece-square table
+    // takes the place of the linear advancement and edge terms.
+    let linear_positional = weights.pst.is_none();
+    for ((row, col), &piece) in board.indexed_iter() {
+        if piece == player {
+            // Material value
+            score += weights.piece_value;
+
+            if linear_positional {
+                // Advancement
+                let advancement = if player == BLACK {
+                    row as f64
+                } else {
+                    (BOARD_SIZE - 1 - row) as f64
+                };
+                score += weights.advancement_value * advancement;
+
+                // Edge pawn bonus
+                if is_edge_square(row, col) {
+                    score += weights.edge_pawn_bonus;
+                }
+            }
+        } else if piece == -player {
+            // Opponent's material value
+            score -= weights.piece_value;
+
+            if linear_positional {
+                // Opponent's advancement
+                let advancement = if player == BLACK {
+                    (BOARD_SIZE - 1 - row) as f64
+                } else {
+                    row as f64
+                };
+                score -= weights.advancement_value * advancement;
+
+                // Opponent's edge pawn bonus
+                if is_edge_square(row, col) {
+                    score -= weights.edge_pawn_bonus;
+                }
+            }
+        }
+    }
+
+    // Piece-square table
+    if let Some(pst) = &weights.pst {
+        score += pst.eval(board, player);
+    }
+
+    // Center control
+    score += weights.center_control_value
+        * (count_center_control(board, player) - count_center_control(board, -player));
+
+    // Mobility
+    if with_mobility {
+        let mobility = compute_mobility(board, player) as f64
+            - compute_mobility(board, -player) as f64;
+        score += weights.mobility_value * mobility;
+    }
+
+    // Connectivity
+    score += weights.connectivity_value
+        * (connectivity_score(board, player) - connectivity_score(board, -player));
+
+    // Pieces stuck behind a friendly piece
+    score -= weights.blocked_pawn_penalty
+        * (count_blocked_pawns(board, player) as f64 - count_blocked_pawns(board, -player) as f64);
+
+    // Pieces the other side can capture
+    score -= weights.threatened_penalty
+        * (count_threatened_pieces(board, player) as f64
+            - count_threatened_pieces(board, -player) as f64);
+
+    // Unstoppable pawns
+    let ai_unstoppable_pawns = get_unstoppable_pawns_steps(board, player);
+    let opponent_unstoppable_pawns = get_unstoppable_pawns_steps(board, -player);
+
+    // Evaluate our unstoppable pawns
+    for steps in ai_unstoppable_pawns.iter() {
+        let bonus = weights.unstoppable_pawn_bonus / (*steps as f64 + 1.0);
+        score += bonus;
+    }
+
+    // Evaluate opponent's unstoppable pawns
+    for steps in opponent_unstoppable_pawns.iter() {
+        let penalty = weights.opponent_unstoppable_pawn_penalty / (*steps as f64 + 1.0);
+        score += penalty; // Since penalty is negative
+    }
+
+    // Promotion race: reward the side whose fastest unstoppable pawn is
+    // ahead, scaled by the lead. A side without one counts as BOARD_SIZE
+    // steps away, and a one-step lead is ignored since it can come down to
+    // who moves first.
+    let no_racer = BOARD_SIZE as isize;
+    let min_ai_steps = ai_unstoppable_pawns.iter().min().copied().unwrap_or(no_racer);
+    let min_opponent_steps = opponent_unstoppable_pawns.iter().min().copied().unwrap_or(no_racer);
+    let lead = min_opponent_steps - min_ai_steps;
+    if lead.abs() >= 2 {
+        score += weights.promotion_race_bonus * lead as f64;
+    }
+
+    // Promotion proximity: the closer a side's pieces are to the last row on
+    // average, the larger the bonus
+    score += weights.promotion_proximity_value
+        * (promotion_proximity(board, player) - promotion_proximity(board, -player));
+
+    score
+}
+
+// Moves a piece of `player` at `pos` needs to reach the last row on an empty
+// board. Every forward move or capture gains exactly one row and sideways
+// moves gain none, so this is the distance in rows.
+pub fn min_steps_to_promotion(pos: (usize, usize), player: i32) -> usize {
+    let (row, _) = pos;
+    if player == BLACK {
+        BOARD_SIZE - 1 - row
+    } else {
+        row
+    }
+}
+
+// 1 / the average of `min_steps_to_promotion` over the pieces of `player`,
+// or 0 without pieces
+fn promotion_proximity(board: &Array2<i32>, player: i32) -> f64 {
+    let steps: Vec<usize> = board
+        .indexed_iter()
+        .filter(|&(_, &piece)| piece == player)
+        .map(|(pos, _)| min_steps_to_promotion(pos, player))
+        .collect();
+    if steps.is_empty() {
+        return 0.0;
+    }
+    let average_steps = steps.iter().sum::<usize>() as f64 / steps.len() as f64;
+    // A piece on the last row has already won
+    1.0 / average_steps.max(1.0)
+}
+
+// Hidden layer width of `NnEval`
+const NN_HIDDEN: usize = 64;
+const NN_INPUTS: usize = BOARD_SIZE * BOARD_SIZE;
+
+// Small fully connected evaluation network: one input per square (+1 for
+// the side to move's pieces, -1 for the opponent's), a ReLU hidden layer
+// and a single output scored like `evaluate_board`
+pub struct NnEval {
+    weights_layer1: Vec<Vec<f64>>, // NN_HIDDEN rows of NN_INPUTS weights
+    biases_layer1: Vec<f64>,
+    weights_layer2: Vec<f64>,
+    bias_layer2: f64,
+}
+
+impl NnEval {
+    // Load weights stored as little-endian f64s: the first layer row by row,
+    // its biases, the second layer weights and finally its bias
+    pub fn from_file(path: &str) -> Result<Self, NnError> {
+        let data = fs::read(path).map_err(NnError::Io)?;
+        let expected = (NN_HIDDEN * NN_INPUTS + 2 * NN_HIDDEN + 1) * 8;
+        if data.len() != expected {
+            return Err(NnError::WrongSize { expected, actual: data.len() });
+        }
+
+        let mut values = data.chunks_exact(8).map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()));
+        let mut take = |n: usize| values.by_ref().take(n).collect::<Vec<f64>>();
+        let weights_layer1 = (0..NN_HIDDEN).map(|_| take(NN_INPUTS)).collect();
+        let biases_layer1 = take(NN_HIDDEN);
+        let weights_layer2 = take(NN_HIDDEN);
+        let bias_layer2 = take(1)[0];
+        Ok(NnEval { weights_layer1, biases_layer1, weights_layer2, bias_layer2 })
+    }
+
+    // Evaluation of `board` for `player`
+    pub fn eval(&self, board: &Array2<i32>, player: i32) -> f64 {
+        let inputs: Vec<f64> = board.iter().map(|&piece| (piece * player) as f64).collect();
+        let mut output = self.bias_layer2;
+        for ((weights, bias), output_weight) in
+            self.weights_layer1.iter().zip(&self.biases_layer1).zip(&self.weights_layer2)
+        {
+            let activation: f64 = weights.iter().zip(&inputs).map(|(w, x)| w * x).sum::<f64>() + bias;
+            output += activation.max(0.0) * output_weight;
+        }
+        output
+    }
+}
+
+impl Evaluator for NnEval {
+    fn evaluate(&self, board: &Array2<i32>, player: i32) -> f64 {
+        self.eval(board, player)
+    }
+}
+
+#[derive(Debug)]
+pub enum NnError {
+    Io(io::Error),
+    WrongSize { expected: usize, actual: usize }, // In bytes
+}
+
+impl std::fmt::Display for NnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NnError::Io(err) => write!(f, "cannot read network weights: {}", err),
+            NnError::WrongSize { expected, actual } => {
+                write!(f, "network weights file is {} bytes, expected {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NnError {}
+
+// Number of legal moves available to `player`
+fn compute_mobility(board: &Array2<i32>, player: i32) -> usize {
+    get_valid_moves(board, player).len()
+}
+
+// Number of pairs of `player` pieces that are orthogonally or diagonally
+// adjacent
+fn connectivity_score(board: &Array2<i32>, player: i32) -> f64 {
+    // Only look forward and right so each pair is counted once
+    let neighbours = [(0, 1), (1, -1), (1, 0), (1, 1)];
+    let mut pairs = 0;
+
+    for ((row, col), &piece) in board.indexed_iter() {
+        if piece != player {
+            continue;
+        }
+        for (dr, dc) in neighbours {
+            let new_row = row as isize + dr;
+            let new_col = col as isize + dc;
+            if is_within_bounds(new_row, new_col)
+                && board[[new_row as usize, new_col as usize]] == player
+            {
+                pairs += 1;
+            }
+        }
+    }
+
+    pairs as f64
+}
+
+// Number of `player` pieces with a friendly piece directly in front of them
+fn count_blocked_pawns(board: &Array2<i32>, player: i32) -> usize {
+    board
+        .indexed_iter()
+        .filter(|&((row, col), &piece)| {
+            let ahead_row = row as isize + player as isize; // BLACK advances +1, WHITE -1
+            piece == player
+                && is_within_bounds(ahead_row, col as isize)
+                && board[[ahead_row as usize, col]] == player
+        })
+        .count()
+}
+
+// Number of `player` pieces the opponent could capture next move
+fn count_threatened_pieces(board: &Array2<i32>, player: i32) -> usize {
+    get_capture_targets(board, -player).len()
+}
+
+// Squares of the opponent's pieces that `player` can capture right now. Each
+// opponent piece is checked for an attacker diagonally behind it with an
+// empty square diagonally beyond.
+pub fn get_capture_targets(board: &Array2<i32>, player: i32) -> HashSet<(usize, usize)> {
+    let forward = if player == BLACK { 1 } else { -1 };
+    board
+        .indexed_iter()
+        .filter(|&((row, col), &piece)| {
+            piece == -player
+                && [-1, 1].iter().any(|&side| {
+                    let from_row = row as isize - forward;
+                    let from_col = col as isize - side;
+                    let to_row = row as isize + forward;
+                    let to_col = col as isize + side;
+                    is_within_bounds(from_row, from_col)
+                        && is_within_bounds(to_row, to_col)
+                        && board[[from_row as usize, from_col as usize]] == player
+                        && board[[to_row as usize, to_col as usize]] == EMPTY
+                })
+        })
+        .map(|(square, _)| square)
+        .collect()
+}
+
+// Central 3x3 region: rows 3-5, columns 3-5
+fn is_center_square(row: usize, col: usize) -> bool {
+    (3..=5).contains(&row) && (3..=5).contains(&col)
+}
+
+// Pieces `player` has in the center plus empty center squares it can reach
+// in one move
+fn count_center_control(board: &Array2<i32>, player: i32) -> f64 {
+    let mut occupied = 0;
+    let mut reachable = HashSet::new();
+
+    for ((row, col), &piece) in board.indexed_iter() {
+        if piece != player {
+            continue;
+        }
+        if is_center_square(row, col) {
+            occupied += 1;
+        }
+        let (piece_moves, piece_capture_moves) = get_piece_moves(board, (row, col), player);
+        for Move { to_row, to_col, .. } in piece_moves.into_iter().chain(piece_capture_moves) {
+            if is_center_square(to_row, to_col) {
+                reachable.insert((to_row, to_col));
+            }
+        }
+    }
+
+    (occupied + reachable.len()) as f64
+}
+
+fn is_edge_square(_row: usize, col: usize) -> bool {
+    col == 0 || col == BOARD_SIZE - 1
+}
+
+pub(crate) fn get_winner(board: &Array2<i32>) -> Option<i32> {
+    // Check if BLACK has won
+    for col in 0..BOARD_SIZE {
+        if board[[BOARD_SIZE - 1, col]] == BLACK {
+            return Some(BLACK);
+        }
+    }
+
+    // Check if WHITE has won
+    for col in 0..BOARD_SIZE {
+        if board[[0, col]] == WHITE {
+            return Some(WHITE);
+        }
+    }
+
+    // Check if either player has no pieces left
+    let mut black_pieces = 0;
+    let mut white_pieces = 0;
+    for &piece in board.iter() {
+        if piece == BLACK {
+            black_pieces += 1;
+        } else if piece == WHITE {
+            white_pieces += 1;
+        }
+    }
+
+    if black_pieces == 0 {
+        return Some(WHITE);
+    }
+    if white_pieces == 0 {
+        return Some(BLACK);
+    }
+
+    None
+}
+
+// Where a game stands with `player` to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    BlackWon,
+    WhiteWon,
+    // The player to move has no legal moves, which loses the game
+    Stalemate(i32),
+}
+
+// True if the game is not yet won and `player` has no legal moves
+pub fn is_stalemate(board: &Array2<i32>, player: i32) -> bool {
+    get_winner(board).is_none() && MoveGen::new(board, player).next().is_none()
+}
+
+pub fn game_status(board: &Array2<i32>, player: i32) -> GameStatus {
+    match get_winner(board) {
+        Some(BLACK) => GameStatus::BlackWon,
+        Some(_) => GameStatus::WhiteWon,
+        None if is_stalemate(board, player) => GameStatus::Stalemate(player),
+        None => GameStatus::Ongoing,
+    }
+}
+
+pub(crate) fn get_valid_moves(board: &Array2<i32>, player: i32) -> Vec<Move> {
+    MoveGen::new(board, player).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MovePhase {
+    Captures,
+    Quiets,
+    Done,
+}
+
+// Lazy version of `get_valid_moves`, yielding the same moves in the same
+// order. Captures are generated a piece at a time as they are consumed; quiet
+// moves only start once the whole board has been scanned without finding a
+// capture.
+pub struct MoveGen<'a> {
+    board: &'a Array2<i32>,
+    player: i32,
+    row: usize, // Next square to generate moves from
+    col: usize,
+    phase: MovePhase,
+    buffer: VecDeque<Move>, // Generated but not yet yielded
+    found_capture: bool,
+}
+
+impl<'a> MoveGen<'a> {
+    pub fn new(board: &'a Array2<i32>, player: i32) -> Self {
+        MoveGen {
+            board,
+            player,
+            row: 0,
+            col: 0,
+            phase: MovePhase::Captures,
+            buffer: VecDeque::new(),
+            found_capture: false,
+        }
+    }
+
+    // Fill the buffer from the next square holding one of our pieces with
+    // moves of the current phase. Returns false once the board is exhausted.
+    fn generate_next_square(&mut self) -> bool {
+        while self.row < BOARD_SIZE {
+            let square = (self.row, self.col);
+            self.col += 1;
+            if self.col == BOARD_SIZE {
+                self.col = 0;
+                self.row += 1;
+            }
+            if self.board[[square.0, square.1]] != self.player {
+                continue;
+            }
+            let (quiet_moves, capture_moves) = get_piece_moves(self.board, square, self.player);
+            match self.phase {
+                MovePhase::Captures => self.buffer.extend(capture_moves),
+                _ => self.buffer.extend(quiet_moves),
+            }
+            if !self.buffer.is_empty() {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for MoveGen<'_> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        loop {
+            if let Some(mv) = self.buffer.pop_front() {
+                if self.phase == MovePhase::Captures {
+                    self.found_capture = true;
+                }
+                return Some(mv);
+            }
+            if self.phase == MovePhase::Done {
+                return None;
+            }
+            if !self.generate_next_square() {
+                // Quiet moves are only legal when there was no capture
+                self.phase = match self.phase {
+                    MovePhase::Captures if !self.found_capture => MovePhase::Quiets,
+                    _ => MovePhase::Done,
+                };
+                self.row = 0;
+                self.col = 0;
+            }
+        }
+    }
+}
+
+// (black pieces, white pieces) on the board
+pub fn count_pieces(board: &Array2<i32>) -> (usize, usize) {
+    let black_count = board.iter().filter(|&&piece| piece == BLACK).count();
+    let white_count = board.iter().filter(|&&piece| piece == WHITE).count();
+    (black_count, white_count)
+}
+
+// `player`'s pieces minus the opponent's
+pub fn piece_difference(board: &Array2<i32>, player: i32) -> i32 {
+    let (black_count, white_count) = count_pieces(board);
+    let difference = black_count as i32 - white_count as i32;
+    if player == BLACK { difference } else { -difference }
+}
+
+type Square = (usize, usize);
+
+// Destinations of a piece on one square with the bounds checks already done:
+// single steps (forward, left, right) and captures as (jumped square,
+// landing square) pairs, in the order `get_piece_moves` yields them
+struct SquareMoves {
+    steps: Vec<Square>,
+    jumps: Vec<(Square, Square)>,
+}
+
+// `SquareMoves` for every square, for Black ([0]) and White ([1])
+struct MoveTable {
+    squares: [Vec<SquareMoves>; 2],
+}
+
+impl MoveTable {
+    fn new() -> Self {
+        let side = |forward: isize| {
+            let offset = |(row, col): Square, dr: isize, dc: isize| {
+                let (row, col) = (row as isize + dr, col as isize + dc);
+                is_within_bounds(row, col).then_some((row as usize, col as usize))
+            };
+            (0..BOARD_SIZE * BOARD_SIZE)
+                .map(|index| {
+                    let square = (index / BOARD_SIZE, index % BOARD_SIZE);
+                    let steps = [(forward, 0), (0, -1), (0, 1)]
+                        .iter()
+                        .filter_map(|&(dr, dc)| offset(square, dr, dc))
+                        .collect();
+                    let jumps = [-1, 1]
+                        .iter()
+                        .filter_map(|&dc| {
+                            Some((offset(square, forward, dc)?, offset(square, 2 * forward, 2 * dc)?))
+                        })
+                        .collect();
+                    SquareMoves { steps, jumps }
+                })
+                .collect()
+        };
+        MoveTable { squares: [side(1), side(-1)] }
+    }
+
+    fn get(&self, (row, col): Square, player: i32) -> Option<&SquareMoves> {
+        let side = match player {
+            BLACK => 0,
+            WHITE => 1,
+            _ => return None,
+        };
+        self.squares[side].get(row * BOARD_SIZE + col)
+    }
+}
+
+fn move_table() -> &'static MoveTable {
+    static MOVE_TABLE: OnceLock<MoveTable> = OnceLock::new();
+    MOVE_TABLE.get_or_init(MoveTable::new)
+}
+
+// (quiet moves, captures) of the piece on `pos`. Quiet moves are left out
+// when the piece has a capture.
+fn get_piece_moves(
+    board: &Array2<i32>,
+    pos: (usize, usize),
+    player: i32,
+) -> (Vec<Move>, Vec<Move>) {
+    let Some(square_moves) = move_table().get(pos, player) else {
+        return (Vec::new(), Vec::new());
+    };
+    let (row, col) = pos;
+
+    // Capture moves
+    let capture_moves: Vec<Move> = square_moves
+        .jumps
+        .iter()
+        .filter(|&&((mid_row, mid_col), (to_row, to_col))| {
+            board[[mid_row, mid_col]] == -player && board[[to_row, to_col]] == EMPTY
+        })
+        .map(|&(_, (to_row, to_col))| Move::new(row, col, to_row, to_col))
+        .collect();
+    if !capture_moves.is_empty() {
+        return (Vec::new(), capture_moves);
+    }
+
+    // Normal moves
+    let moves = square_moves
+        .steps
+        .iter()
+        .filter(|&&(to_row, to_col)| board[[to_row, to_col]] == EMPTY)
+        .map(|&(to_row, to_col)| Move::new(row, col, to_row, to_col))
+        .collect();
+    (moves, Vec::new())
+}
+
+fn is_within_bounds(row: isize, col: isize) -> bool {
+    row >= 0 && row < BOARD_SIZE as isize && col >= 0 && col < BOARD_SIZE as isize
+}
+
+// Checks a single move against the rules without generating the full move
+// list. Quiet moves still need a scan for captures, which are mandatory.
+pub fn is_legal_move(board: &Array2<i32>, mv: Move, player: i32) -> bool {
+    let Move { from_row, from_col, to_row, to_col } = mv;
+    if from_row >= BOARD_SIZE || from_col >= BOARD_SIZE || to_row >= BOARD_SIZE || to_col >= BOARD_SIZE {
+        return false;
+    }
+    if (player != BLACK && player != WHITE)
+        || board[[from_row, from_col]] != player
+        || board[[to_row, to_col]] != EMPTY
+    {
+        return false;
+    }
+
+    let forward = if player == BLACK { 1 } else { -1 };
+    let row_step = to_row as isize - from_row as isize;
+    let col_step = to_col as isize - from_col as isize;
+    if row_step == 2 * forward && col_step.abs() == 2 {
+        board[[(from_row + to_row) / 2, (from_col + to_col) / 2]] == -player
+    } else if (row_step == forward && col_step == 0) || (row_step == 0 && col_step.abs() == 1) {
+        !has_capture(board, player)
+    } else {
+        false
+    }
+}
+
+fn has_capture(board: &Array2<i32>, player: i32) -> bool {
+    let forward = if player == BLACK { 1 } else { -1 };
+    board.indexed_iter().any(|((row, col), &piece)| {
+        piece == player
+            && [-1, 1].iter().any(|&side| {
+                let mid_row = row as isize + forward;
+                let mid_col = col as isize + side;
+                let new_row = row as isize + 2 * forward;
+                let new_col = col as isize + 2 * side;
+                is_within_bounds(new_row, new_col)
+                    && board[[mid_row as usize, mid_col as usize]] == -player
+                    && board[[new_row as usize, new_col as usize]] == EMPTY
+            })
+    })
+}
+
+// Count the leaf nodes exactly `depth` plies below `board`, for checking
+// move generation. Finished games are not expanded. Moves are made and
+// undone in place, and `zobrist_hash` is kept up to date incrementally,
+// which debug builds check at every node.
+fn perft(board: &mut Array2<i32>, depth: usize, player: i32, zobrist_hash: &mut u64, zobrist_table: &ZobristTable) -> u64 {
+    debug_assert_eq!(compute_zobrist_hash(board, zobrist_table), *zobrist_hash, "incremental Zobrist hash out of sync");
+    if depth == 0 {
+        return 1;
+    }
+    if get_winner(board).is_some() {
+        return 0;
+    }
+
+    let mut nodes = 0;
+    for mv in get_valid_moves(board, player) {
+        let captured_piece = make_move(board, &mv, player, zobrist_hash, zobrist_table);
+        nodes += perft(board, depth - 1, -player, zobrist_hash, zobrist_table);
+        undo_move(board, &mv, captured_piece, zobrist_hash, zobrist_table);
+    }
+    nodes
+}
+
+// Perft split by root move
+pub fn perft_divide(board: &Array2<i32>, depth: usize, player: i32) -> Vec<(Move, u64)> {
+    let zobrist_table = ZobristTable::new_random();
+    if depth == 0 || get_winner(board).is_some() {
+        return Vec::new();
+    }
+    let zobrist_hash = compute_zobrist_hash(board, &zobrist_table);
+
+    get_valid_moves(board, player)
+        .into_iter()
+        .map(|mv| {
+            let mut new_board = board.clone();
+            let mut new_hash = zobrist_hash;
+            make_move(&mut new_board, &mv, player, &mut new_hash, &zobrist_table);
+            (mv, perft(&mut new_board, depth - 1, -player, &mut new_hash, &zobrist_table))
+        })
+        .collect()
+}
+
+// Same count as `perft`, with the subtree of each root move counted on the
+// Rayon thread pool
+pub fn perft_threaded(board: &Array2<i32>, depth: usize, player: i32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if get_winner(board).is_some() {
+        return 0;
+    }
+    let zobrist_table = ZobristTable::new_random();
+    let zobrist_hash = compute_zobrist_hash(board, &zobrist_table);
+
+    get_valid_moves(board, player)
+        .par_iter()
+        .map(|mv| {
+            let mut new_board = board.clone();
+            let mut new_hash = zobrist_hash;
+            make_move(&mut new_board, mv, player, &mut new_hash, &zobrist_table);
+            perft(&mut new_board, depth - 1, -player, &mut new_hash, &zobrist_table)
+        })
+        .sum()
+}
+
+pub fn perft_move_string(mv: &Move) -> String {
+    format!("({},{})->({},{})", mv.from_row, mv.from_col, mv.to_row, mv.to_col)
+}
+
+// Middlegame positions from engine games, half with Black to move
+pub fn default_benchmark_positions() -> Vec<&'static str> {
+    vec![
+        "BBBBB.BBB/.B....B.B/..B...B../...B.B.../........./...WW..../..W...W../.W..W..W./WWW.WWWWW W",
+        "BBB.BBBBB/.B.....B./...B...B./..BB.B.../........./...W.W.../..W...W.W/.WW.W..W./WW.W.WW.W B",
+        "BBB.BBBBB/.B.B...B./..B...B../........./....B..../...W.W.../..W...W../WW.....W./.WWW.WWWW W",
+        "BBBB.B.B./.B..B.B.B/..B....B./...BB.B../........./...W.WW../..W.....W/..WW..W../WWW.WWW.W B",
+        "BB.B.B.B./.BB.BB.BB/......B../...B.B.../..B....../..W.WW.../..W....W./.W..W.WW./WW.W.W.WW W",
+        ".BB.B.BB./.B.B.B.B./..B...B.B/B..BB..../........./...W.WW.W/..W....W./WW.W.W.../W.W.W.WW. B",
+        "BB.BB..BB/.....B..B/.BB....../....BBB../........./.....W.../W.....W../..W....W./WW.WWWWWW W",
+        "B.B.B.BBB/...B.B.B./.B....B../..B.BB.../........./..WW..W../.....W..W/.W.W..W../WWW..W.WW B",
+        ".BB.BB.B./BB.B..B.B/.......B./.B.B..B../........./...W.W.../.W..W..WB/W.W.W.W../.W.W.W.WW W",
+        "B.B.BB.BB/.B.B...B./.B...B.B./...B...../...W.B.../.....W.../.W.W...W./..W..W.W./WW.WW.W.W B",
+        "BB..B.BBB/.BBB...B./..B....B./..B..B.../.....W.../.W.W..W../...WW..../W.W...W../.W.W..WWW W",
+        "BB.B.B.BB/B..B...B./........./B.....B.B/........./.....W.W./..WW...../.W...W.W./W.W.W.W.W B",
+        ".B.B.B..B/B.B.B..B./.B......B/....BBB../..W....../..W...WW./........./W..W...W./.WW.W.WWW W",
+        ".B..B.BB./B.B.....B/.B....BB./.....BB../..W....../...W.WW../........./.W.W..WWW/W.W.W.W.. B",
+        ".B...B.BB/B..B....B/..B..B.../...B.B.../........./.....W.../...W...../W.W.W.W.W/.W.W.W.WW W",
+        "B.B..B.BB/WB..B.B../...B...../......B../...B...../......W../.......W./W....WW.W/WWW.W..W. B",
+        "B.B.B.B../.B.B...BB/.B.....B./........./...B.W.../W....W.../.W......W/W..W.W.../.WW..W..W W",
+        ".BB.B...B/B.B..B.B./......B../..B.B.B../W...W..B./...W...W./W.....W../.W...W.W./W.W...W.W B",
+        "B.B..B.../....B.B../..BB...B./.B....B../...B...../...W.W.W./.W......./W....W.W./WW....W.W W",
+        "B.BB.B.B./..B.....B/.B......./..B.B..../........./....W..../WW.....W./......WWW/WW...W... B",
+    ]
+}
+
+// Totals of a `benchmark` run, and the nodes and milliseconds per position
+#[derive(Debug, Clone, Default)]
+pub struct BenchmarkResult {
+    pub total_nodes: u64,
+    pub total_time_ms: u64,
+    pub nps: u64,
+    pub results: Vec<(String, u64, u64)>,
+}
+
+// Search each FEN position to `depth` with fresh tables and the default
+// weights, so that runs are comparable between engine versions
+pub fn benchmark(positions: &[String], depth: i32) -> Result<BenchmarkResult, FenError> {
+    let config = SearchConfig { max_depth: depth, ..SearchConfig::default() };
+    let evaluator = HandcraftedEvaluator { weights: Weights::default() };
+    let zobrist_table = ZobristTable::new_random();
+    let mut benchmark_result = BenchmarkResult::default();
+    let start_time = Instant::now();
+
+    for fen in positions {
+        let (board, player) = parse_fen(fen)?;
+        let mut transposition_table = new_transposition_table(&config);
+        let clock = SearchClock::new(Duration::MAX, None);
+        let result = iterative_deepening(
+            &board,
+            player,
+            &evaluator,
+            &config,
+            &zobrist_table,
+            transposition_table.as_mut(),
+            &[],
+            0,
+            &clock,
+            None,
+        );
+        benchmark_result.results.push((fen.clone(), result.nodes, result.time_ms));
+        benchmark_result.total_nodes += result.nodes;
+    }
+
+    let elapsed = start_time.elapsed();
+    benchmark_result.total_time_ms = elapsed.as_millis() as u64;
+    benchmark_result.nps = (benchmark_result.total_nodes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64;
+    Ok(benchmark_result)
+}
+
+const NUM_SQUARES: usize = BOARD_SIZE * BOARD_SIZE;
+const BOARD_MASK: u128 = (1 << NUM_SQUARES) - 1;
+const COL_0: u128 = column_mask(0);
+const COL_1: u128 = column_mask(1);
+const COL_7: u128 = column_mask(BOARD_SIZE - 2);
+const COL_8: u128 = column_mask(BOARD_SIZE - 1);
+const ROW_0: u128 = row_mask(0);
+const ROW_8: u128 = row_mask(BOARD_SIZE - 1);
+
+const fn column_mask(col: usize) -> u128 {
+    let mut mask = 0;
+    let mut row = 0;
+    while row < BOARD_SIZE {
+        mask |= 1 << (row * BOARD_SIZE + col);
+        row += 1;
+    }
+    mask
+}
+
+const fn row_mask(row: usize) -> u128 {
+    ((1 << BOARD_SIZE) - 1) << (row * BOARD_SIZE)
+}
+
+// Bitboard representation of the board: bit `row * 9 + col` is set in
+// `black` or `white` when that player has a piece on the square
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitBoard {
+    pub black: u128,
+    pub white: u128,
+}
+
+impl BitBoard {
+    pub fn from_array2(board: &Array2<i32>) -> Self {
+        let mut bitboard = BitBoard { black: 0, white: 0 };
+        for ((row, col), &piece) in board.indexed_iter() {
+            let bit = 1 << (row * BOARD_SIZE + col);
+            match piece {
+                BLACK => bitboard.black |= bit,
+                WHITE => bitboard.white |= bit,
+                _ => {}
+            }
+        }
+        bitboard
+    }
+
+    pub fn to_array2(&self) -> Array2<i32> {
+        Array2::from_shape_fn((BOARD_SIZE, BOARD_SIZE), |(row, col)| self.piece_at(row, col))
+    }
+
+    fn pieces(&self, player: i32) -> u128 {
+        match player {
+            BLACK => self.black,
+            WHITE => self.white,
+            _ => 0,
+        }
+    }
+
+    pub fn piece_at(&self, row: usize, col: usize) -> i32 {
+        let bit = 1 << (row * BOARD_SIZE + col);
+        if self.black & bit != 0 {
+            BLACK
+        } else if self.white & bit != 0 {
+            WHITE
+        } else {
+            EMPTY
+        }
+    }
+
+    pub fn count_pieces(&self, player: i32) -> usize {
+        self.pieces(player).count_ones() as usize
+    }
+
+    // Same rules as `get_winner`
+    pub fn get_winner(&self) -> Option<i32> {
+        if self.black & ROW_8 != 0 {
+            Some(BLACK)
+        } else if self.white & ROW_0 != 0 || self.black == 0 {
+            Some(WHITE)
+        } else if self.white == 0 {
+            Some(BLACK)
+        } else {
+            None
+        }
+    }
+
+    // Captures are mandatory, so normal moves are only returned when no
+    // capture is available
+    pub fn get_valid_moves(&self, player: i32) -> Vec<Move> {
+        let capture_moves = self.get_capture_moves(player);
+        if !capture_moves.is_empty() {
+            capture_moves
+        } else {
+            self.get_normal_moves(player)
+        }
+    }
+
+    // Diagonal forward jumps over an adjacent opponent piece
+    pub fn get_capture_moves(&self, player: i32) -> Vec<Move> {
+        let own = self.pieces(player);
+        let opponent = self.pieces(-player);
+        let empty = !(self.black | self.white) & BOARD_MASK;
+        let mut moves = Vec::new();
+
+        match player {
+            BLACK => {
+                let left = ((((own & !(COL_0 | COL_1)) << 8) & opponent) << 8) & empty;
+                let right = ((((own & !(COL_7 | COL_8)) << 10) & opponent) << 10) & empty;
+                push_bitboard_moves(&mut moves, left, 16);
+                push_bitboard_moves(&mut moves, right, 20);
+            }
+            WHITE => {
+                let left = ((((own & !(COL_0 | COL_1)) >> 10) & opponent) >> 10) & empty;
+                let right = ((((own & !(COL_7 | COL_8)) >> 8) & opponent) >> 8) & empty;
+                push_bitboard_moves(&mut moves, left, -20);
+                push_bitboard_moves(&mut moves, right, -16);
+            }
+            _ => {}
+        }
+
+        // Same order as `get_valid_moves`: by piece, then by direction
+        moves.sort_by_key(|mv| mv.from_row * BOARD_SIZE + mv.from_col);
+        moves
+    }
+
+    // One step forward or sideways onto an empty square
+    pub fn get_normal_moves(&self, player: i32) -> Vec<Move> {
+        let own = self.pieces(player);
+        let empty = !(self.black | self.white) & BOARD_MASK;
+        let mut moves = Vec::new();
+
+        match player {
+            BLACK => push_bitboard_moves(&mut moves, (own << BOARD_SIZE) & empty, BOARD_SIZE as isize),
+            WHITE => push_bitboard_moves(&mut moves, (own >> BOARD_SIZE) & empty, -(BOARD_SIZE as isize)),
+            _ => return moves,
+        }
+        push_bitboard_moves(&mut moves, ((own & !COL_0) >> 1) & empty, -1);
+        push_bitboard_moves(&mut moves, ((own & !COL_8) << 1) & empty, 1);
+
+        moves.sort_by_key(|mv| mv.from_row * BOARD_SIZE + mv.from_col);
+        moves
+    }
+
+    // Play `mv` and return the captured piece, or EMPTY
+    pub fn make_move(&mut self, mv: &Move) -> i32 {
+        let Move { from_row, from_col, to_row, to_col } = *mv;
+        let from_bit = 1 << (from_row * BOARD_SIZE + from_col);
+        let to_bit = 1 << (to_row * BOARD_SIZE + to_col);
+
+        let mut captured_piece = EMPTY;
+        let mid_bit = if from_row.abs_diff(to_row) == 2 {
+            1 << (((from_row + to_row) / 2) * BOARD_SIZE + (from_col + to_col) / 2)
+        } else {
+            0
+        };
+
+        if self.black & from_bit != 0 {
+            self.black ^= from_bit | to_bit;
+            if self.white & mid_bit != 0 {
+                self.white ^= mid_bit;
+                captured_piece = WHITE;
+            }
+        } else if self.white & from_bit != 0 {
+            self.white ^= from_bit | to_bit;
+            if self.black & mid_bit != 0 {
+                self.black ^= mid_bit;
+                captured_piece = BLACK;
+            }
+        }
+
+        captured_piece
+    }
+
+    // Zobrist hash computed straight from the bitboards, equal to
+    // `compute_zobrist_hash` on the matching array
+    pub fn zobrist_hash(&self, zobrist_table: &ZobristTable) -> u64 {
+        let mut hash = 0;
+        for (pieces, piece) in [(self.black, BLACK), (self.white, WHITE)] {
+            let mut remaining = pieces;
+            while remaining != 0 {
+                let square = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                hash ^= zobrist_table[square / BOARD_SIZE][square % BOARD_SIZE][piece_index(piece)];
+            }
+        }
+        hash
+    }
+
+    // Mirror the board left to right
+    pub fn flip(&self) -> BitBoard {
+        BitBoard {
+            black: flip_bits(self.black),
+            white: flip_bits(self.white),
+        }
+    }
+}
+
+// Reverse the order of the squares within each row of a bitboard
+fn flip_bits(bits: u128) -> u128 {
+    let mut flipped = 0;
+    for row in 0..BOARD_SIZE {
+        let shift = row * BOARD_SIZE;
+        let row_bits = ((bits >> shift) as u16) & ((1 << BOARD_SIZE) - 1);
+        let reversed = row_bits.reverse_bits() >> (16 - BOARD_SIZE);
+        flipped |= (reversed as u128) << shift;
+    }
+    flipped
+}
+
+// Add a move for every set bit in `targets`, whose origin is `offset`
+// squares before the target
+fn push_bitboard_moves(moves: &mut Vec<Move>, targets: u128, offset: isize) {
+    let mut remaining = targets;
+    while remaining != 0 {
+        let to = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
+        let from = (to as isize - offset) as usize;
+        moves.push(Move::new(from / BOARD_SIZE, from % BOARD_SIZE, to / BOARD_SIZE, to % BOARD_SIZE));
+    }
+}
+
+// Hashing contract for the search: the root hash is computed once, and every
+// other node gets its hash from its parent through `update_zobrist_hash` (or
+// by XOR with NULL_MOVE_HASH_KEY for a null move), never from scratch; only
+// symmetric TT keys hash the mirrored board. Debug builds check this at every
+// node with `hash_in_sync`.
+fn hash_in_sync(board: &BitBoard, zobrist_hash: u64, zobrist_table: &ZobristTable) -> bool {
+    let hash = board.zobrist_hash(zobrist_table);
+    // Null moves along the path may leave the pass key applied
+    zobrist_hash == hash || zobrist_hash == hash ^ NULL_MOVE_HASH_KEY
+}
+
+// Apply the Zobrist updates for `mv` (played by `piece`, capturing
+// `captured_piece`), mirroring the updates done in `make_move`
+fn update_zobrist_hash(
+    zobrist_hash: &mut u64,
+    mv: &Move,
+    piece: i32,
+    captured_piece: i32,
+    zobrist_table: &ZobristTable,
+) {
+    let Move { from_row, from_col, to_row, to_col } = *mv;
+    *zobrist_hash ^= zobrist_table[from_row][from_col][piece_index(piece)];
+    *zobrist_hash ^= zobrist_table[to_row][to_col][piece_index(piece)];
+    if captured_piece != EMPTY {
+        let mid_row = (from_row + to_row) / 2;
+        let mid_col = (from_col + to_col) / 2;
+        *zobrist_hash ^= zobrist_table[mid_row][mid_col][piece_index(captured_piece)];
+    }
+}
+
+fn get_opponent_pawns_by_row(board: &Array2<i32>, opponent: i32) -> Vec<Vec<usize>> {
+    let mut pawns_by_row: Vec<Vec<usize>> = vec![Vec::new(); BOARD_SIZE];
+    for ((row, col), &piece) in board.indexed_iter() {
+        if piece == opponent {
+            pawns_by_row[row].push(col);
+        }
+    }
+    pawns_by_row
+}
+
+fn is_unstoppable_pawn(
+    pawn_pos: (usize, usize),
+    player: i32,
+    opponent_pawns_by_row: &[Vec<usize>],
+) -> Option<isize> {
+    let (row_pawn, col_pawn) = pawn_pos;
+    let row_pawn = row_pawn as isize;
+    let col_pawn = col_pawn as isize;
+    let row_goal = if player == BLACK { BOARD_SIZE as isize - 1 } else { 0 };
+    let direction = if player == BLACK { 1 } else { -1 };
+
+    let steps_to_goal = (row_goal - row_pawn).abs();
+
+    // Only check rows ahead of the pawn
+    let row_range = if player == BLACK {
+        (row_pawn + 1) as usize..BOARD_SIZE
+    } else {
+        0..(row_pawn as usize)
+    };
+
+    for row in row_range {
+        let relative_row = (row as isize - row_pawn) * direction;
+        let steps_to_opp = relative_row;
+        if steps_to_opp <= 0 {
+            continue;
+        }
+
+        // If steps to opponent pawn exceed steps to goal, they can't stop us
+        if steps_to_opp > steps_to_goal {
+            break;
+        }
+
+        for &col_opp in &opponent_pawns_by_row[row] {
+            let col_diff = (col_opp as isize - col_pawn).abs();
+            if col_diff <= steps_to_opp {
+                // Opponent pawn can reach our pawn
+                return None;
+            }
+        }
+    }
+
+    // No opponent pawns can stop this pawn
+    Some(steps_to_goal)
+}
+
+fn get_unstoppable_pawns_steps(
+    board: &Array2<i32>,
+    player: i32,
+) -> Vec<isize> {
+    let opponent = -player;
+    let opponent_pawns_by_row = get_opponent_pawns_by_row(board, opponent);
+    let mut steps_list = Vec::new();
+
+    for ((row, col), &piece) in board.indexed_iter() {
+        if piece == player {
+            if let Some(steps_to_goal) = is_unstoppable_pawn(
+                (row, col),
+                player,
+                &opponent_pawns_by_row,
+            ) {
+                steps_list.push(steps_to_goal);
+            }
+        }
+    }
+
+    steps_list
+}
+
+// True if the position calls for a closer look: `player` has a capture or a
+// move onto the last row, or the opponent has an unstoppable piece one step
+// from promotion
+pub fn is_forcing_position(board: &Array2<i32>, player: i32) -> bool {
+    let goal_row = if player == BLACK { BOARD_SIZE - 1 } else { 0 };
+    has_capture(board, player)
+        || MoveGen::new(board, player).any(|mv| mv.to_row == goal_row)
+        || get_unstoppable_pawns_steps(board, -player).contains(&1)
+}
+
+// True if the opponent of `player`, who has just moved, can reach its last
+// row with its next move
+pub fn opponent_can_promote_next(board: &Array2<i32>, player: i32) -> bool {
+    let opponent = -player;
+    let goal_row = if opponent == BLACK { BOARD_SIZE - 1 } else { 0 };
+    let penultimate_row = if opponent == BLACK { goal_row - 1 } else { goal_row + 1 };
+    board.row(penultimate_row).iter().any(|&piece| piece == opponent)
+        && MoveGen::new(board, opponent).any(|mv| mv.to_row == goal_row)
+}
+
+// Stand-in for an engine that fails to produce a move; never legal
+pub(crate) const ILLEGAL_MOVE: Move = Move::new(0, 0, 0, 0);
+
+// Outcome of a game played by `play_game`. `winner` is None for a draw.
+#[derive(Debug, Clone)]
+pub struct GameResult {
+    pub winner: Option<i32>,
+    pub moves: Vec<Move>,
+    pub final_board: Array2<i32>,
+    pub ply_count: usize,
+}
+
+// Plays a full game from the starting position, White moving first. The game
+// ends when a player wins, the side to move has no moves or plays an illegal
+// move (a loss), a position repeats three times or `max_moves` plies have
+// been played (a draw).
+pub fn play_game<B, W>(black_engine: B, white_engine: W, max_moves: usize) -> GameResult
+where
+    B: Fn(&Array2<i32>, i32) -> Move,
+    W: Fn(&Array2<i32>, i32) -> Move,
+{
+    let zobrist_table = ZobristTable::new_random();
+    let mut board = starting_board();
+    let mut hash = compute_zobrist_hash(&board, &zobrist_table);
+    let mut position_counts: HashMap<u64, i32> = HashMap::new();
+    position_counts.insert(hash, 1);
+
+    let mut moves = Vec::new();
+    let mut player = WHITE;
+    let mut winner = None;
+
+    while moves.len() < max_moves {
+        if let Some(w) = get_winner(&board) {
+            winner = Some(w);
+            break;
+        }
+
+        let valid_moves = get_valid_moves(&board, player);
+        if valid_moves.is_empty() {
+            winner = Some(-player);
+            break;
+        }
+
+        let mv = if player == BLACK {
+            black_engine(&board, player)
+        } else {
+            white_engine(&board, player)
+        };
+        if !valid_moves.contains(&mv) {
+            winner = Some(-player);
+            break;
+        }
+
+        make_move(&mut board, &mv, player, &mut hash, &zobrist_table);
+        moves.push(mv);
+        player = -player;
+
+        let count = position_counts.entry(hash).or_insert(0);
+        *count += 1;
+        if *count >= 3 {
+            break; // Draw by repetition
+        }
+    }
+
+    // A winning move on the last allowed ply still counts
+    if winner.is_none() {
+        winner = get_winner(&board);
+    }
+
+    GameResult {
+        ply_count: moves.len(),
+        winner,
+        moves,
+        final_board: board,
+    }
+}
+
+// A finished game with its metadata, written and read as PGN-style text.
+// Games always start with White to move, as in `play_game`.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub initial_board: [u8; COMPRESSED_BOARD_SIZE], // Packed by `compress_board`
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+    pub date: String,
+    pub black_engine: String,
+    pub white_engine: String,
+}
+
+#[derive(Debug)]
+pub enum PgnError {
+    Io(io::Error),
+    InvalidTag(String),
+    InvalidFen(FenError),
+    InvalidMove(ParseMoveError),
+    IllegalMove(IllegalMoveError),
+    MissingResult,
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::Io(err) => write!(f, "cannot access game file: {}", err),
+            PgnError::InvalidTag(line) => write!(f, "invalid tag '{}'", line),
+            PgnError::InvalidFen(err) => write!(f, "invalid FEN tag: {}", err),
+            PgnError::InvalidMove(err) => err.fmt(f),
+            PgnError::IllegalMove(err) => err.fmt(f),
+            PgnError::MissingResult => write!(f, "game has no result"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+const PGN_RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+// White is listed first, so "1-0" is a White win
+fn pgn_result(winner: Option<i32>) -> &'static str {
+    match winner {
+        Some(WHITE) => "1-0",
+        Some(BLACK) => "0-1",
+        _ => "1/2-1/2",
+    }
+}
+
+// Tag values are quoted, with '\' escaping quotes and backslashes
+fn pgn_tag(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn parse_pgn_tag(line: &str) -> Option<(&str, String)> {
+    let (name, value) = line.strip_prefix('[')?.strip_suffix(']')?.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name, value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+// Plays `moves` from `initial_board` with White to move first and returns
+// the final board
+pub(crate) fn replay_game(initial_board: &Array2<i32>, moves: &[Move]) -> Result<Array2<i32>, IllegalMoveError> {
+    let mut state = GameState::from_board(initial_board.clone(), WHITE);
+    for &mv in moves {
+        state.apply_move(mv)?;
+    }
+    Ok(state.board)
+}
+
+impl GameRecord {
+    pub fn new(initial_board: &Array2<i32>, result: GameResult, date: &str, black_engine: &str, white_engine: &str) -> Self {
+        GameRecord {
+            initial_board: compress_board(initial_board),
+            moves: result.moves.clone(),
+            result,
+            date: date.to_string(),
+            black_engine: black_engine.to_string(),
+            white_engine: white_engine.to_string(),
+        }
+    }
+
+    // Seven-tag-roster style headers followed by numbered movetext. The FEN
+    // tag is only written for games not starting from the standard setup.
+    pub fn to_pgn_string(&self) -> String {
+        let initial_board = decompress_board(&self.initial_board);
+        let result = pgn_result(self.result.winner);
+
+        let mut pgn = pgn_tag("Date", &self.date);
+        pgn.push_str(&pgn_tag("White", &self.white_engine));
+        pgn.push_str(&pgn_tag("Black", &self.black_engine));
+        pgn.push_str(&pgn_tag("Result", result));
+        if !is_starting_position(&initial_board) {
+            pgn.push_str(&pgn_tag("FEN", &board_to_fen(&initial_board, WHITE)));
+        }
+        pgn.push('\n');
+
+        for (ply, mv) in self.moves.iter().enumerate() {
+            if ply % 2 == 0 {
+                pgn.push_str(&format!("{}. ", ply / 2 + 1));
+            }
+            pgn.push_str(&format_move(*mv));
+            pgn.push(' ');
+        }
+        pgn.push_str(result);
+        pgn.push('\n');
+        pgn
+    }
+
+    // Parses one game. The movetext result wins over the Result tag, and
+    // "*" (unfinished) is read as a draw.
+    pub fn from_pgn_string(s: &str) -> Result<Self, PgnError> {
+        let mut tags = HashMap::new();
+        let mut moves = Vec::new();
+        let mut result = None;
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                let (name, value) = parse_pgn_tag(line).ok_or_else(|| PgnError::InvalidTag(line.to_string()))?;
+                tags.insert(name.to_string(), value);
+                continue;
+            }
+
+            for token in line.split_whitespace() {
+                if PGN_RESULTS.contains(&token) {
+                    result = Some(token.to_string());
+                    break;
+                }
+                // Strip move numbers such as "1." or "1..."
+                let token = token.rsplit('.').next().unwrap_or("");
+                if !token.is_empty() {
+                    moves.push(parse_move(token).map_err(PgnError::InvalidMove)?);
+                }
+            }
+        }
+
+        let winner = match result.or_else(|| tags.get("Result").cloned()).as_deref() {
+            Some("1-0") => Some(WHITE),
+            Some("0-1") => Some(BLACK),
+            Some("1/2-1/2" | "*") => None,
+            _ => return Err(PgnError::MissingResult),
+        };
+        let initial_board = match tags.get("FEN") {
+            Some(fen) => board_from_fen(fen).map_err(PgnError::InvalidFen)?,
+            None => starting_board(),
+        };
+        let final_board = replay_game(&initial_board, &moves).map_err(PgnError::IllegalMove)?;
+        let tag = |name: &str| tags.get(name).cloned().unwrap_or_default();
+
+        Ok(GameRecord {
+            initial_board: compress_board(&initial_board),
+            result: GameResult { winner, moves: moves.clone(), final_board, ply_count: moves.len() },
+            moves,
+            date: tag("Date"),
+            black_engine: tag("Black"),
+            white_engine: tag("White"),
+        })
+    }
+}
+
+pub fn write_pgn_file(records: &[GameRecord], path: &str) -> io::Result<()> {
+    let pgn: Vec<String> = records.iter().map(GameRecord::to_pgn_string).collect();
+    fs::write(path, pgn.join("\n"))
+}
+
+// Games are split after each movetext result token
+pub fn read_pgn_file(path: &str) -> Result<Vec<GameRecord>, PgnError> {
+    let text = fs::read_to_string(path).map_err(PgnError::Io)?;
+    let mut records = Vec::new();
+    let mut game = String::new();
+
+    for line in text.lines() {
+        game.push_str(line);
+        game.push('\n');
+        let ends_game = !line.trim_start().starts_with('[')
+            && line.split_whitespace().any(|token| PGN_RESULTS.contains(&token));
+        if ends_game {
+            records.push(GameRecord::from_pgn_string(&game)?);
+            game.clear();
+        }
+    }
+
+    if !game.trim().is_empty() {
+        records.push(GameRecord::from_pgn_string(&game)?);
+    }
+    Ok(records)
+}
+
+// Self-play games open with this many random plies so they differ
+const SELF_PLAY_RANDOM_PLIES: usize = 4;
+// Self-play games longer than this are recorded as draws
+const SELF_PLAY_MAX_PLIES: usize = 200;
+
+// Plays `n_games` engine-vs-engine games at fixed `depth` and writes every
+// position to a gzip-compressed file. Each record is the board packed by
+// `compress_board` followed by the player to move and the winner (BLACK,
+// WHITE or 0 for a draw) as one signed byte each, 23 bytes in all.
+// Returns the number of records written.
+pub fn generate_self_play_games(
+    n_games: usize,
+    depth: i32,
+    weights: &Weights,
+    output_path: &str,
+) -> io::Result<usize> {
+    let config = SearchConfig { max_depth: depth, ..SearchConfig::default() };
+    let evaluator = HandcraftedEvaluator { weights: weights.clone() };
+    let zobrist_table = ZobristTable::new_random();
+    let mut encoder = GzEncoder::new(io::BufWriter::new(fs::File::create(output_path)?), Compression::default());
+    let mut records = 0;
+
+    for _ in 0..n_games {
+        let transposition_table = RefCell::new(new_transposition_table(&config));
+        let ply = Cell::new(0);
+        let engine = |board: &Array2<i32>, player: i32| -> Move {
+            ply.set(ply.get() + 1);
+            if ply.get() <= SELF_PLAY_RANDOM_PLIES {
+                let moves = get_valid_moves(board, player);
+                return moves[rand::thread_rng().gen_range(0..moves.len())];
+            }
+            let result = iterative_deepening(
+                board,
+                player,
+                &evaluator,
+                &config,
+                &zobrist_table,
+                transposition_table.borrow_mut().as_mut(),
+                &[],
+                0,
+                &SearchClock::new(Duration::MAX, None),
+                None,
+            );
+            result.best_move.unwrap_or(ILLEGAL_MOVE)
+        };
+        let result = play_game(engine, engine, SELF_PLAY_MAX_PLIES);
+
+        // Replay the game to recover the position before each move
+        let outcome = result.winner.unwrap_or(0);
+        let mut board = starting_board();
+        let mut player = WHITE;
+        for mv in &result.moves {
+            encoder.write_all(&compress_board(&board))?;
+            encoder.write_all(&[player as i8 as u8, outcome as i8 as u8])?;
+            records += 1;
+
+            let mut hash = 0;
+            make_move(&mut board, mv, player, &mut hash, &zobrist_table);
+            player = -player;
+        }
+    }
+
+    encoder.finish()?.flush()?;
+    Ok(records)
+}
+
+// Score of engine A against engine B over a `run_tournament` match
+#[derive(Debug, Clone, Default)]
+pub struct TournamentResult {
+    pub wins_a: u32,
+    pub wins_b: u32,
+    pub draws: u32,
+    pub elo_diff: f64, // Positive when A is stronger
+}
+
+// Elo difference implied by a match score. A perfect or zero score counts
+// as half a game less or more so the estimate stays finite.
+pub fn elo_difference(wins: u32, losses: u32, draws: u32) -> f64 {
+    let games = (wins + losses + draws) as f64;
+    if games == 0.0 {
+        return 0.0;
+    }
+    let score = (wins as f64 + draws as f64 / 2.0) / games;
+    let score = score.clamp(0.5 / games, 1.0 - 0.5 / games);
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+// Plays `n_games` between two search configurations sharing `weights`. Games
+// come in pairs that open with the same random plies, A playing White in the
+// first game of each pair and Black in the second.
+pub fn run_tournament(
+    engine_a_config: &SearchConfig,
+    engine_b_config: &SearchConfig,
+    n_games: usize,
+    weights: &Weights,
+) -> TournamentResult {
+    let evaluator = HandcraftedEvaluator { weights: weights.clone() };
+    let zobrist_table = ZobristTable::new_random();
+    let configs = [engine_a_config, engine_b_config];
+    let mut result = TournamentResult::default();
+    let mut opening = Vec::new();
+
+    for game in 0..n_games {
+        if game % 2 == 0 {
+            opening = random_opening(SELF_PLAY_RANDOM_PLIES);
+        }
+        let a_plays_white = game % 2 == 0;
+
+        let transposition_tables = configs.map(|config| RefCell::new(new_transposition_table(config)));
+        let ply = Cell::new(0);
+        // Move for the engine at `index` in `configs`
+        let search = |board: &Array2<i32>, player: i32, index: usize| -> Move {
+            ply.set(ply.get() + 1);
+            if let Some(&mv) = opening.get(ply.get() - 1) {
+                return mv;
+            }
+            let config = configs[index];
+            iterative_deepening(
+                board,
+                player,
+                &evaluator,
+                config,
+                &zobrist_table,
+                transposition_tables[index].borrow_mut().as_mut(),
+                &[],
+                0,
+                &SearchClock::new(Duration::from_secs_f64(config.time_limit_secs), None),
+                None,
+            )
+            .best_move
+            .unwrap_or(ILLEGAL_MOVE)
+        };
+        let (white_index, black_index) = if a_plays_white { (0, 1) } else { (1, 0) };
+        let game_result = play_game(
+            |board, player| search(board, player, black_index),
+            |board, player| search(board, player, white_index),
+            SELF_PLAY_MAX_PLIES,
+        );
+
+        match game_result.winner {
+            None => result.draws += 1,
+            Some(winner) if (winner == WHITE) == a_plays_white => result.wins_a += 1,
+            Some(_) => result.wins_b += 1,
+        }
+    }
+
+    result.elo_diff = elo_difference(result.wins_a, result.wins_b, result.draws);
+    result
+}
+
+// Up to `plies` random moves from the starting position, stopping early if
+// the game ends
+fn random_opening(plies: usize) -> Vec<Move> {
+    let mut rng = rand::thread_rng();
+    let mut board = starting_board();
+    let mut player = WHITE;
+    let mut moves = Vec::new();
+    while moves.len() < plies && get_winner(&board).is_none() {
+        let valid_moves = get_valid_moves(&board, player);
+        if valid_moves.is_empty() {
+            break;
+        }
+        let mv = valid_moves[rng.gen_range(0..valid_moves.len())];
+        make_move(&mut board, &mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+        moves.push(mv);
+        player = -player;
+    }
+    moves
+}
+
+// A root move with its search score, for stochastic move selection
+pub type WeightedMove = (Move, f64);
+
+// Sample a move with probability softmax(scores / temperature). A
+// temperature of 0.0 falls back to the highest score. None if there are no
+// moves; `scores` must have one score per move.
+pub fn sample_move(moves: &[Move], scores: &[f64], temperature: f64) -> Option<Move> {
+    assert_eq!(moves.len(), scores.len(), "sample_move needs one score per move");
+    sample_index(scores, temperature, &mut rand::thread_rng()).map(|index| moves[index])
+}
+
+fn sample_index(scores: &[f64], temperature: f64, rng: &mut (impl Rng + ?Sized)) -> Option<usize> {
+    let best_index = (0..scores.len()).max_by(|&a, &b| scores[a].total_cmp(&scores[b]))?;
+    if temperature <= 0.0 {
+        return Some(best_index);
+    }
+
+    // Subtract the best score so the exponentials cannot overflow
+    let max_score = scores[best_index];
+    let probabilities: Vec<f64> = scores.iter().map(|score| ((score - max_score) / temperature).exp()).collect();
+    let mut target = rng.gen::<f64>() * probabilities.iter().sum::<f64>();
+    for (index, probability) in probabilities.iter().enumerate() {
+        target -= probability;
+        if target < 0.0 {
+            return Some(index);
+        }
+    }
+    Some(best_index)
+}
+
+// Score every root move with a full-window search, deepening until
+// `config.max_depth` or the time limit. Scores come from the last depth
+// completed for all moves.
+pub fn root_move_scores(
+    board: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
+) -> Vec<WeightedMove> {
+    let zobrist_table = ZobristTable::new_random();
+    let mut transposition_table = new_transposition_table(config);
+    let clock = SearchClock::new(Duration::from_secs_f64(config.time_limit_secs), None);
+    let moves = get_valid_moves(board, player);
+    let (scores, _) = score_root_moves(
+        board,
+        player,
+        evaluator,
+        config,
+        moves,
+        &zobrist_table,
+        transposition_table.as_mut(),
+        &clock,
+    );
+    scores
+}
+
+// `root_move_scores` for the root moves `moves`. Also returns the last depth
+// completed.
+#[allow(clippy::too_many_arguments)]
+fn score_root_moves(
+    board: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
+    moves: Vec<Move>,
+    zobrist_table: &ZobristTable,
+    transposition_table: &mut dyn TranspositionStore,
+    clock: &SearchClock,
+) -> (Vec<WeightedMove>, i32) {
+    let mut scores: Vec<WeightedMove> = moves.iter().map(|&mv| (mv, 0.0)).collect();
+    if moves.is_empty() || get_winner(board).is_some() {
+        return (scores, 0);
+    }
+
+    let mut completed_depth = 0;
+    let mut pv_table = PvTable::new();
+    let mut killers: KillerTable = [[None; 2]; MAX_DEPTH];
+    let bitboard = BitBoard::from_array2(board);
+    let zobrist_hash = bitboard.zobrist_hash(zobrist_table);
+
+    // Depths completed regardless of the time limit, at least the first so
+    // every move is scored by a search
+    let min_depth = config.min_depth.min(config.max_depth).max(1);
+
+    'deepening: for depth in 1..=config.max_depth {
+        clock.set_ignore_time_limit(depth <= min_depth);
+        let mut depth_scores = Vec::with_capacity(moves.len());
+        for &mv in &moves {
+            let mut new_board = bitboard;
+            let mut new_hash = zobrist_hash;
+            let captured_piece = new_board.make_move(&mv);
+            update_zobrist_hash(&mut new_hash, &mv, player, captured_piece, zobrist_table);
+
+            let mut zobrist_stack = ZobristStack::new();
+            zobrist_stack.push(zobrist_hash);
+            let (eval, _) = negamax_search(
+                &new_board,
+                depth - 1,
+                -player,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+                evaluator,
+                true,
+                new_hash,
+                zobrist_table,
+                transposition_table,
+                config.use_symmetry,
+                &mut zobrist_stack,
+                clock,
+                None,
+                config.null_move.as_ref(),
+                config.lmr.as_ref(),
+                config.lmp_threshold,
+                config.futility_margin,
+                config.razoring_margin,
+                config.delta_margin,
+                &mut killers,
+                &mut pv_table,
+                None,
+                None,
+                None,
+                Some(mv),
+                1,
+                next_half_move_clock(0, &mv),
+                false,
+                config.use_extensions,
+                config.use_forced_capture_extension,
+                &mut 0,
+                config.use_threat_extension,
+                &mut 0,
+            );
+            if clock.expired() {
+                break 'deepening;
+            }
+            depth_scores.push((mv, -eval));
+        }
+        scores = depth_scores;
+        completed_depth = depth;
+        if depth >= min_depth && clock.soft_expired(config.soft_time_fraction) {
+            break;
+        }
+    }
+    clock.set_ignore_time_limit(false);
+    (scores, completed_depth)
+}
+
+// Forward pruning at the root: only the `beam_width` moves with the best
+// static evaluation are searched, each with the usual alpha-beta below it
+#[allow(clippy::too_many_arguments)]
+fn beam_search(
+    board: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
+    beam_width: usize,
+    zobrist_table: &ZobristTable,
+    transposition_table: &mut dyn TranspositionStore,
+    clock: &SearchClock,
+) -> SearchResult {
+    let mut candidates = static_move_scores(&BitBoard::from_array2(board), player, evaluator);
+    candidates.truncate(beam_width.max(1));
+    let moves = candidates.into_iter().map(|(mv, _)| mv).collect();
+
+    let (scores, completed_depth) =
+        score_root_moves(board, player, evaluator, config, moves, zobrist_table, transposition_table, clock);
+    // Ties go to the move with the better static evaluation
+    let best = scores.into_iter().reduce(|best, candidate| if candidate.1 > best.1 { candidate } else { best });
+    match best {
+        Some((mv, score)) => SearchResult::new(Some(mv), score, PrincipalVariation { moves: vec![mv] }, completed_depth),
+        None => SearchResult::new(None, LOSE_SCORE, PrincipalVariation::default(), 0),
+    }
+}
+
+// Best move of a `depth` ply beam search keeping `beam_width` root moves
+pub fn beam_search_root(board: &Array2<i32>, player: i32, depth: i32, beam_width: usize, weights: &Weights) -> Option<Move> {
+    let config = SearchConfig { max_depth: depth, beam_width: Some(beam_width), ..SearchConfig::default() };
+    let evaluator = HandcraftedEvaluator { weights: weights.clone() };
+    let zobrist_table = ZobristTable::new_random();
+    let mut transposition_table = new_transposition_table(&config);
+    let clock = SearchClock::new(Duration::MAX, None);
+    beam_search(board, player, &evaluator, &config, beam_width, &zobrist_table, transposition_table.as_mut(), &clock)
+        .best_move
+}
+
+// Policy targets from a `depth` ply search of every root move: one
+// BOARD_SIZE x BOARD_SIZE array for the squares moved from and one for the
+// squares moved to. Each square holds the best score of the moves using it
+// and -inf if no move does.
+pub fn policy_logits(
+    board: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    depth: i32,
+) -> (Array2<f32>, Array2<f32>) {
+    let config = SearchConfig { max_depth: depth, ..SearchConfig::default() };
+    let zobrist_table = ZobristTable::new_random();
+    let mut transposition_table = new_transposition_table(&config);
+    let clock = SearchClock::new(Duration::MAX, None);
+    let moves = get_valid_moves(board, player);
+    let (scores, _) = score_root_moves(
+        board,
+        player,
+        evaluator,
+        &config,
+        moves,
+        &zobrist_table,
+        transposition_table.as_mut(),
+        &clock,
+    );
+
+    let mut from_logits = Array2::from_elem((BOARD_SIZE, BOARD_SIZE), f32::NEG_INFINITY);
+    let mut to_logits = from_logits.clone();
+    for (mv, score) in scores {
+        let from = &mut from_logits[[mv.from_row, mv.from_col]];
+        *from = from.max(score as f32);
+        let to = &mut to_logits[[mv.to_row, mv.to_col]];
+        *to = to.max(score as f32);
+    }
+    (from_logits, to_logits)
+}
+
+// Node of an analysis tree. `eval` is from the point of view of `player`, the
+// side to move, and comes from a search of `depth` plies.
+#[derive(Debug, Clone)]
+pub struct GameTreeNode {
+    pub board: Array2<i32>,
+    pub player: i32,
+    pub mv_from_parent: Option<Move>,
+    pub eval: f64,
+    pub children: Vec<GameTreeNode>,
+    pub depth: i32,
+}
+
+impl GameTreeNode {
+    pub fn new(board: Array2<i32>, player: i32) -> Self {
+        GameTreeNode { board, player, mv_from_parent: None, eval: 0.0, children: Vec::new(), depth: 0 }
+    }
+
+    // Adds a child for every legal move, each scored by a `depth` ply search
+    // with the engine's evaluator, and takes the best of them as this node's
+    // evaluation. Children are ordered best first. A finished game gets no
+    // children.
+    pub fn expand(&mut self, engine: &Engine, depth: i32) {
+        let config = SearchConfig { max_depth: depth.max(1), ..engine.config.clone() };
+        let mut scores = if get_winner(&self.board).is_some() {
+            Vec::new()
+        } else {
+            root_move_scores(&self.board, self.player, engine.evaluator.as_ref(), &config)
+        };
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        self.children = scores
+            .into_iter()
+            .map(|(mv, score)| {
+                let mut board = self.board.clone();
+                make_move(&mut board, &mv, self.player, &mut 0, &NO_ZOBRIST_KEYS);
+                GameTreeNode {
+                    board,
+                    player: -self.player,
+                    mv_from_parent: Some(mv),
+                    eval: -score,
+                    children: Vec::new(),
+                    depth: config.max_depth - 1,
+                }
+            })
+            .collect();
+        self.depth = config.max_depth;
+        self.eval = match get_winner(&self.board) {
+            Some(winner) => if winner == self.player { f64::INFINITY } else { f64::NEG_INFINITY },
+            None => self.best_child().map_or(f64::NEG_INFINITY, |child| -child.eval),
+        };
+    }
+
+    fn best_child(&self) -> Option<&GameTreeNode> {
+        self.children.iter().min_by(|a, b| a.eval.total_cmp(&b.eval))
+    }
+
+    // Follows the best child from this node down to a leaf
+    pub fn best_line(&self) -> Vec<Move> {
+        let mut line = Vec::new();
+        let mut node = self;
+        while let Some(child) = node.best_child() {
+            line.extend(child.mv_from_parent);
+            node = child;
+        }
+        line
+    }
+
+    // The tree as PGN movetext: the best line as the main line, other moves
+    // as variations, each move annotated with the mover's evaluation. Move
+    // numbers count from this node.
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        let ply = if self.player == WHITE { 0 } else { 1 };
+        write_tree_pgn(self, ply, true, &mut pgn);
+        pgn.push('*');
+        pgn
+    }
+}
+
+fn write_tree_pgn(node: &GameTreeNode, ply: usize, show_number: bool, pgn: &mut String) {
+    let Some(best) = node.best_child() else {
+        return;
+    };
+    let write_move = |child: &GameTreeNode, show_number: bool, pgn: &mut String| {
+        if ply.is_multiple_of(2) {
+            pgn.push_str(&format!("{}. ", ply / 2 + 1));
+        } else if show_number {
+            pgn.push_str(&format!("{}... ", ply / 2 + 1));
+        }
+        if let Some(mv) = child.mv_from_parent {
+            pgn.push_str(&format_move(mv));
+        }
+        pgn.push_str(&format!(" {{{:.2}}} ", -child.eval + 0.0)); // Avoids "-0.00"
+    };
+
+    write_move(best, show_number, pgn);
+    let mut alternatives = node.children.iter().filter(|child| !std::ptr::eq(*child, best)).peekable();
+    let has_alternatives = alternatives.peek().is_some();
+    for child in alternatives {
+        pgn.push('(');
+        write_move(child, true, pgn);
+        write_tree_pgn(child, ply + 1, false, pgn);
+        if pgn.ends_with(' ') {
+            pgn.pop();
+        }
+        pgn.push_str(") ");
+    }
+    write_tree_pgn(best, ply + 1, has_alternatives, pgn);
+}
+
+// Scale of the logistic curve mapping evaluations to expected scores
+const TEXEL_K: f64 = 1.0;
+// Step used for the numerical derivative of the error
+const TEXEL_DELTA: f64 = 0.01;
+// Amount a weight is moved per accepted coordinate-descent step
+const TEXEL_STEP: f64 = 1.0;
+
+// Expected score for the side to move given an evaluation
+fn texel_sigmoid(evaluation: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-TEXEL_K * evaluation / 400.0))
+}
+
+// Mean squared error between predicted and actual results
+fn texel_error(positions: &[(Array2<i32>, i32, f64)], weights: &Weights) -> f64 {
+    let total: f64 = positions
+        .par_iter()
+        .map(|(board, player, result)| {
+            let predicted = texel_sigmoid(evaluate_board(board, *player, weights, true));
+            (predicted - result).powi(2)
+        })
+        .sum();
+    total / positions.len().max(1) as f64
+}
+
+// Texel tuning: coordinate descent over every weight to minimise the error
+// of `texel_error`. Each position is (board, player to move, result) with the
+// result from that player's side: 1 for a win, 0.5 for a draw, 0 for a loss.
+pub fn texel_tune(positions: &[(Array2<i32>, i32, f64)], initial_weights: Weights, iterations: usize) -> Weights {
+    let mut weights = initial_weights;
+    let mut best_error = texel_error(positions, &weights);
+
+    for _ in 0..iterations {
+        let mut improved = false;
+
+        for index in 0..Weights::NAMES.len() {
+            let original = *weights.values_mut()[index];
+
+            // Numerical derivative of the error with respect to this weight
+            *weights.values_mut()[index] = original + TEXEL_DELTA;
+            let error_up = texel_error(positions, &weights);
+            *weights.values_mut()[index] = original - TEXEL_DELTA;
+            let error_down = texel_error(positions, &weights);
+            let gradient = (error_up - error_down) / (2.0 * TEXEL_DELTA);
+            if gradient == 0.0 {
+                *weights.values_mut()[index] = original;
+                continue;
+            }
+
+            // Step downhill and keep the change only if the error drops
+            *weights.values_mut()[index] = original - TEXEL_STEP * gradient.signum();
+            let error = texel_error(positions, &weights);
+            if error < best_error {
+                best_error = error;
+                improved = true;
+            } else {
+                *weights.values_mut()[index] = original;
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    weights
+}
+
+// UCB1 exploration constant
+const MCTS_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+// Random playouts longer than this are scored as draws
+const MCTS_MAX_ROLLOUT_PLIES: usize = 200;
+
+// Picks the moves of MCTS playouts. `moves` is never empty.
+pub trait RolloutPolicy {
+    fn choose_move(&self, board: &BitBoard, player: i32, moves: &[Move], rng: &mut dyn RngCore) -> Move;
+}
+
+// Uniformly random playouts
+pub struct RandomRolloutPolicy;
+
+impl RolloutPolicy for RandomRolloutPolicy {
+    fn choose_move(&self, _board: &BitBoard, _player: i32, moves: &[Move], rng: &mut dyn RngCore) -> Move {
+        moves[rng.gen_range(0..moves.len())]
+    }
+}
+
+// Playouts that sample each move with probability softmax(score /
+// temperature), scoring the position after the move with `evaluate_board`
+pub struct BiasedRolloutPolicy {
+    pub weights: Weights,
+    pub temperature: f64,
+}
+
+impl RolloutPolicy for BiasedRolloutPolicy {
+    fn choose_move(&self, board: &BitBoard, player: i32, moves: &[Move], rng: &mut dyn RngCore) -> Move {
+        let scores: Vec<f64> = moves
+            .iter()
+            .map(|mv| {
+                let mut new_board = *board;
+                new_board.make_move(mv);
+                evaluate_board(&new_board.to_array2(), player, &self.weights, false)
+            })
+            .collect();
+        moves[sample_index(&scores, self.temperature, rng).expect("rollout moves are never empty")]
+    }
+}
+
+// Node of the Monte Carlo search tree. `wins` is counted for the player who
+// made the move into this node (1 per win, 0.5 per draw).
+struct MctsNode {
+    board: BitBoard,
+    player: i32, // Player to move in this node
+    mv: Option<Move>, // Move that led here from the parent
+    visits: u32,
+    wins: f64,
+    children: Vec<MctsNode>,
+    untried_moves: Vec<Move>,
+}
+
+impl MctsNode {
+    fn new(board: BitBoard, player: i32, mv: Option<Move>) -> Self {
+        let untried_moves = if board.get_winner().is_some() {
+            Vec::new()
+        } else {
+            board.get_valid_moves(player)
+        };
+        MctsNode {
+            board,
+            player,
+            mv,
+            visits: 0,
+            wins: 0.0,
+            children: Vec::new(),
+            untried_moves,
+        }
+    }
+
+    // One selection / expansion / simulation / backpropagation pass. Returns
+    // the result for the player who moved into this node.
+    fn iterate(&mut self, policy: &dyn RolloutPolicy, rng: &mut impl Rng) -> f64 {
+        let result = if let Some(winner) = self.board.get_winner() {
+            if winner == -self.player { 1.0 } else { 0.0 }
+        } else if !self.untried_moves.is_empty() {
+            // Expansion: add one untried move and play it out
+            let index = rng.gen_range(0..self.untried_moves.len());
+            let mv = self.untried_moves.swap_remove(index);
+            let mut new_board = self.board;
+            new_board.make_move(&mv);
+
+            let mut child = MctsNode::new(new_board, -self.player, Some(mv));
+            let child_result = child.rollout(policy, rng);
+            child.visits += 1;
+            child.wins += child_result;
+            self.children.push(child);
+            1.0 - child_result
+        } else if self.children.is_empty() {
+            // No legal moves: the player to move loses
+            1.0
+        } else {
+            // Selection: descend into the child with the best UCB1 score
+            let log_visits = (self.visits as f64).ln();
+            let child = self
+                .children
+                .iter_mut()
+                .max_by(|a, b| a.ucb1(log_visits).total_cmp(&b.ucb1(log_visits)))
+                .unwrap();
+            1.0 - child.iterate(policy, rng)
+        };
+
+        self.visits += 1;
+        self.wins += result;
+        result
+    }
+
+    fn ucb1(&self, parent_log_visits: f64) -> f64 {
+        self.wins / self.visits as f64
+            + MCTS_EXPLORATION * (parent_log_visits / self.visits as f64).sqrt()
+    }
+
+    // Play moves chosen by `policy` to the end of the game. Returns the
+    // result for the player who moved into this node.
+    fn rollout(&self, policy: &dyn RolloutPolicy, rng: &mut impl Rng) -> f64 {
+        let mut board = self.board;
+        let mut player = self.player;
+
+        for _ in 0..MCTS_MAX_ROLLOUT_PLIES {
+            if let Some(winner) = board.get_winner() {
+                return if winner == -self.player { 1.0 } else { 0.0 };
+            }
+            let moves = board.get_valid_moves(player);
+            if moves.is_empty() {
+                return if player == self.player { 1.0 } else { 0.0 };
+            }
+            board.make_move(&policy.choose_move(&board, player, &moves, rng));
+            player = -player;
+        }
+
+        0.5
+    }
+}
+
+// Monte Carlo Tree Search with UCT and random rollouts. Runs until either
+// `simulations` iterations are done or `time_limit` has passed, then returns
+// the most visited move and its win rate for `player`.
+pub fn mcts_search(board: &Array2<i32>, player: i32, time_limit: Duration, simulations: u32) -> Option<(Move, f64)> {
+    mcts_search_with_policy(board, player, time_limit, simulations, &RandomRolloutPolicy)
+}
+
+// `mcts_search` with the playouts chosen by `policy`
+pub fn mcts_search_with_policy(
+    board: &Array2<i32>,
+    player: i32,
+    time_limit: Duration,
+    simulations: u32,
+    policy: &dyn RolloutPolicy,
+) -> Option<(Move, f64)> {
+    // A time limit too long to represent is no time limit
+    let deadline = Instant::now().checked_add(time_limit);
+    let mut rng = rand::thread_rng();
+
+    let mut root = MctsNode::new(BitBoard::from_array2(board), player, None);
+    while root.visits < simulations && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+        root.iterate(policy, &mut rng);
+    }
+
+    let best_child = root.children.iter().max_by_key(|child| child.visits)?;
+    Some((best_child.mv?, best_child.wins / best_child.visits as f64))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Board from its rows, row 0 first, in `board_from_fen` notation
+    fn board(rows: [&str; BOARD_SIZE]) -> Array2<i32> {
+        board_from_fen(&format!("{} W", rows.join("/"))).unwrap()
+    }
+
+    fn sorted_moves(board: &Array2<i32>, player: i32) -> Vec<Move> {
+        let mut moves = get_valid_moves(board, player);
+        moves.sort_by_key(|mv| (mv.from_row, mv.from_col, mv.to_row, mv.to_col));
+        moves
+    }
+
+    #[test]
+    fn starting_position_move_counts() {
+        let board = starting_board();
+        assert_eq!(get_valid_moves(&board, WHITE).len(), 25);
+        assert_eq!(get_valid_moves(&board, BLACK).len(), 25);
+        assert!(get_valid_moves(&board, WHITE).iter().all(|mv| !is_capture(mv)));
+    }
+
+    #[test]
+    fn starting_position_from_fen() {
+        let board = board([
+            "BBBBBBBBB",
+            ".B.....B.",
+            "..B...B..",
+            "...B.B...",
+            ".........",
+            "...W.W...",
+            "..W...W..",
+            ".W.....W.",
+            "WWWWWWWWW",
+        ]);
+        assert_eq!(board, starting_board());
+    }
+
+    #[test]
+    fn forced_capture_excludes_quiet_moves() {
+        let board = board([
+            "B........",
+            ".........",
+            ".........",
+            "...B.....",
+            "....W....",
+            ".........",
+            ".........",
+            ".........",
+            "W........",
+        ]);
+        assert_eq!(get_valid_moves(&board, WHITE), vec![Move::new(4, 4, 2, 2)]);
+    }
+
+    #[test]
+    fn capture_in_both_directions() {
+        let board = board([
+            "B........",
+            ".........",
+            ".........",
+            "...B.B...",
+            "....W....",
+            ".........",
+            ".........",
+            ".........",
+            "W........",
+        ]);
+        assert_eq!(sorted_moves(&board, WHITE), vec![Move::new(4, 4, 2, 2), Move::new(4, 4, 2, 6)]);
+    }
+
+    #[test]
+    fn all_capturing_pieces_are_returned() {
+        let board = board([
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".B.....B.",
+            "W.......W",
+            ".........",
+            ".........",
+            ".........",
+        ]);
+        assert_eq!(sorted_moves(&board, WHITE), vec![Move::new(5, 0, 3, 2), Move::new(5, 8, 3, 6)]);
+        // Black's jumps over the same pieces would land off the board
+        assert!(get_valid_moves(&board, BLACK).iter().all(|mv| !is_capture(mv)));
+    }
+
+    #[test]
+    fn blocked_landing_square_allows_quiet_moves() {
+        let board = board([
+            "B........",
+            ".........",
+            "..B......",
+            "...B.....",
+            "....W....",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+        ]);
+        assert_eq!(
+            sorted_moves(&board, WHITE),
+            vec![Move::new(4, 4, 3, 4), Move::new(4, 4, 4, 3), Move::new(4, 4, 4, 5)]
+        );
+    }
+
+    #[test]
+    fn no_backward_captures() {
+        let board = board([
+            "B........",
+            ".........",
+            ".........",
+            ".........",
+            "....W....",
+            "...B.....",
+            ".........",
+            ".........",
+            ".........",
+        ]);
+        assert!(get_valid_moves(&board, WHITE).iter().all(|mv| !is_capture(mv)));
+        assert_eq!(get_valid_moves(&board, WHITE).len(), 3);
+    }
+
+    #[test]
+    fn edge_piece_moves() {
+        let board = board([
+            "B........",
+            ".........",
+            ".........",
+            ".........",
+            "W........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+        ]);
+        assert_eq!(sorted_moves(&board, WHITE), vec![Move::new(4, 0, 3, 0), Move::new(4, 0, 4, 1)]);
+    }
+
+    #[test]
+    fn black_on_last_row_wins() {
+        let board = board([
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "....W....",
+            ".........",
+            "....B....",
+        ]);
+        assert_eq!(get_winner(&board), Some(BLACK));
+    }
+
+    #[test]
+    fn white_on_first_row_wins() {
+        let board = board([
+            "....W....",
+            ".........",
+            "....B....",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+        ]);
+        assert_eq!(get_winner(&board), Some(WHITE));
+        assert_eq!(get_winner(&starting_board()), None);
+    }
+
+    #[test]
+    fn no_moves_position() {
+        let board = board([
+            ".........",
+            ".........",
+            "..B......",
+            "BB.......",
+            "WB.......",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+        ]);
+        assert!(get_valid_moves(&board, WHITE).is_empty());
+        assert_eq!(get_winner(&board), None);
+        assert!(is_stalemate(&board, WHITE));
+    }
+
+    #[test]
+    fn move_generators_agree() {
+        let board = starting_board();
+        for player in [BLACK, WHITE] {
+            let mut bitboard_moves = BitBoard::from_array2(&board).get_valid_moves(player);
+            bitboard_moves.sort_by_key(|mv| (mv.from_row, mv.from_col, mv.to_row, mv.to_col));
+            assert_eq!(bitboard_moves, sorted_moves(&board, player));
+            assert_eq!(MoveGen::new(&board, player).collect::<Vec<_>>(), get_valid_moves(&board, player));
+        }
+    }
+
+    fn is_capture(mv: &Move) -> bool {
+        mv.from_row.abs_diff(mv.to_row) == 2
+    }
+
+    // Undecided position after up to `max_plies` random moves from the start
+    #[cfg(debug_assertions)]
+    fn random_position(rng: &mut impl Rng, max_plies: usize) -> Option<Array2<i32>> {
+        let mut board = starting_board();
+        let mut player = WHITE;
+        for _ in 0..rng.gen_range(0..=max_plies) {
+            let moves = get_valid_moves(&board, player);
+            if moves.is_empty() || get_winner(&board).is_some() {
+                break;
+            }
+            let mv = moves[rng.gen_range(0..moves.len())];
+            make_move(&mut board, &mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+            player = -player;
+        }
+        get_winner(&board).is_none().then_some(board)
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn evaluation_is_symmetric() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(96);
+        let pst_weights = Weights { pst: Some(PieceSquareTable::from_weights(&Weights::default())), ..Weights::default() };
+        let mut checked = 0;
+        while checked < 50 {
+            if let Some(board) = random_position(&mut rng, 80) {
+                assert_evaluation_symmetry(&board, &Weights::default());
+                assert_evaluation_symmetry(&board, &pst_weights);
+                checked += 1;
+            }
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn zugzwang_evaluation_is_symmetric() {
+        // White's only piece must step next to a Black piece that captures it
+        let board = board([
+            ".........",
+            ".........",
+            ".........",
+            "..B......",
+            "..W......",
+            ".........",
+            "B........",
+            ".........",
+            ".........",
+        ]);
+        assert!(in_zugzwang(&board, WHITE) && !in_zugzwang(&board, BLACK));
+        assert_evaluation_symmetry(&board, &Weights::default());
+    }
+
+    #[test]
+    fn repeated_search_keeps_principal_variation() {
+        // The second search finds the root in the transposition table
+        let mut engine = Engine::new(Weights::default(), SearchConfig { max_depth: 4, ..SearchConfig::default() });
+        let time = TimeManager::fixed(Duration::from_secs(60));
+        for _ in 0..2 {
+            let result = engine.best_move(&starting_board(), WHITE, &time);
+            assert_eq!(result.pv.moves.first(), result.best_move.as_ref());
+            assert_eq!(result.pv.len(), 4);
+            assert_eq!(engine.get_pv(), result.pv.moves);
+        }
+    }
+
+    #[test]
+    fn sibling_paths_do_not_count_as_repetitions() {
+        let root = board([
+            ".........",
+            ".........",
+            "...B...B.",
+            ".........",
+            ".........",
+            ".........",
+            ".W...W...",
+            ".........",
+            ".........",
+        ]);
+        let zobrist_table = ZobristTable::new_seeded(85);
+        let root_hash = compute_zobrist_hash(&root, &zobrist_table);
+        let step = |from_row, from_col, to_col| Move { from_row, from_col, to_row: from_row, to_col };
+        let white_a = step(6, 1, 0);
+        let white_b = step(6, 5, 4);
+        let black_a = step(2, 3, 2);
+        let black_b = step(2, 7, 8);
+
+        let mut zobrist_stack = ZobristStack::new();
+        zobrist_stack.push(root_hash);
+        // Both orders reach the same position; search the second after the first is unwound
+        let mut leaf_hashes = Vec::new();
+        for path in [[white_a, black_a, white_b, black_b], [white_b, black_b, white_a, black_a]] {
+            let mut position = root.clone();
+            let mut hash = root_hash;
+            for (ply, mv) in path.iter().enumerate() {
+                let player = if ply % 2 == 0 { WHITE } else { BLACK };
+                make_move(&mut position, mv, player, &mut hash, &zobrist_table);
+                zobrist_stack.push(hash);
+            }
+            assert_eq!(zobrist_stack.count_occurrences(hash), 1);
+            leaf_hashes.push(hash);
+            for _ in path {
+                zobrist_stack.pop();
+            }
+            assert_eq!(zobrist_stack.history(), &[] as &[u64]);
+            assert_eq!(zobrist_stack.count_occurrences(root_hash), 1);
+        }
+        assert_eq!(leaf_hashes[0], leaf_hashes[1]);
+    }
+
+    fn serial_perft(board: &Array2<i32>, depth: usize, player: i32) -> u64 {
+        perft_divide(board, depth, player).iter().map(|&(_, nodes)| nodes).sum()
+    }
+
+    #[test]
+    fn perft_from_starting_position() {
+        for (depth, nodes) in [(1, 25), (2, 623), (3, 14_975), (4, 356_399)] {
+            assert_eq!(serial_perft(&starting_board(), depth, WHITE), nodes, "perft({depth})");
+        }
+    }
+
+    #[test]
+    fn perft_depth_5_from_starting_position() {
+        // Millions of nodes; split across threads to keep debug builds bearable
+        assert_eq!(perft_threaded(&starting_board(), 5, WHITE), 8_419_237);
+    }
+
+    #[test]
+    fn threaded_perft_matches_serial() {
+        for fen in default_benchmark_positions().into_iter().take(4) {
+            let (board, player) = parse_fen(fen).unwrap();
+            for depth in 1..=3 {
+                assert_eq!(perft_threaded(&board, depth, player), serial_perft(&board, depth, player), "{fen} at depth {depth}");
+            }
+        }
+    }
+
+    // Weights that score nothing but the terms `set` turns on
+    fn weights_with(set: impl FnOnce(&mut Weights)) -> Weights {
+        let mut weights = Weights::default();
+        for value in weights.values_mut() {
+            *value = 0.0;
+        }
+        set(&mut weights);
+        weights
+    }
+
+    #[test]
+    fn center_control_counts() {
+        let central = board([
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "....B....",
+            ".........",
+            ".........",
+            ".........",
+            "........W",
+        ]);
+        // The piece itself and its three moves, all central
+        assert_eq!(count_center_control(&central, BLACK), 4.0);
+        assert_eq!(count_center_control(&central, WHITE), 0.0);
+
+        let shared = board([
+            ".........",
+            ".........",
+            ".........",
+            "...B.B...",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "........W",
+        ]);
+        // Both pieces reach d4 (3, 4), which is only counted once
+        assert_eq!(count_center_control(&shared, BLACK), 5.0);
+
+        let approaching = board([
+            ".........",
+            ".........",
+            "....B....",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "........W",
+        ]);
+        assert_eq!(count_center_control(&approaching, BLACK), 1.0);
+    }
+
+    #[test]
+    fn center_heavy_position_scores_higher() {
+        let center = board([
+            ".........",
+            ".........",
+            ".........",
+            "...B.B...",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "W.......W",
+        ]);
+        let edge = board([
+            ".........",
+            ".........",
+            ".........",
+            "B.......B",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "W.......W",
+        ]);
+        let weights = weights_with(|weights| weights.center_control_value = 1.0);
+        assert!(evaluate_board(&center, BLACK, &weights, true) > evaluate_board(&edge, BLACK, &weights, true));
+    }
+
+    #[test]
+    fn connectivity_counts_adjacent_pairs() {
+        let grouped = board([
+            ".........",
+            ".........",
+            ".........",
+            "...BB....",
+            "...BB....",
+            ".........",
+            "W........",
+            "W........",
+            ".........",
+        ]);
+        // Two rows, two columns and two diagonals
+        assert_eq!(connectivity_score(&grouped, BLACK), 6.0);
+        assert_eq!(connectivity_score(&grouped, WHITE), 1.0);
+
+        let spread = board([
+            ".........",
+            ".........",
+            ".........",
+            "B..B.....",
+            "......B.B",
+            ".........",
+            "W........",
+            "W........",
+            ".........",
+        ]);
+        assert_eq!(connectivity_score(&spread, BLACK), 0.0);
+
+        let weights = weights_with(|weights| weights.connectivity_value = 1.0);
+        assert!(evaluate_board(&grouped, BLACK, &weights, true) > evaluate_board(&spread, BLACK, &weights, true));
+    }
+
+    #[test]
+    fn blocked_pawn_counts() {
+        let position = board([
+            ".........",
+            ".........",
+            "........B",
+            "...B....W",
+            "...B.....",
+            "...B..W..",
+            "......W..",
+            ".........",
+            ".........",
+        ]);
+        // Black's rear two pieces in the column, and White's rear piece.
+        // A piece in front of an opponent's piece does not block it.
+        assert_eq!(count_blocked_pawns(&position, BLACK), 2);
+        assert_eq!(count_blocked_pawns(&position, WHITE), 1);
+    }
+
+    #[test]
+    fn starting_position_is_valid() {
+        let start = starting_board();
+        assert_eq!(validate_board(&start), Ok(()));
+        assert!(is_starting_position(&start));
+        assert_eq!(get_winner(&start), None);
+    }
+
+    #[test]
+    fn invalid_boards_are_rejected() {
+        assert_eq!(
+            validate_board(&Array2::zeros((BOARD_SIZE - 1, BOARD_SIZE))),
+            Err(BoardError::WrongDimensions { rows: BOARD_SIZE - 1, cols: BOARD_SIZE }),
+        );
+
+        let mut bad_value = starting_board();
+        bad_value[[4, 4]] = 2;
+        assert_eq!(validate_board(&bad_value), Err(BoardError::InvalidPieceValue(2)));
+
+        let mut extra_piece = starting_board();
+        extra_piece[[4, 4]] = WHITE;
+        assert_eq!(validate_board(&extra_piece), Err(BoardError::TooManyPieces { player: WHITE, count: 16 }));
+
+        // A piece moved back towards its own side
+        let mut retreated = starting_board();
+        retreated[[3, 3]] = EMPTY;
+        retreated[[1, 4]] = BLACK;
+        assert!(!is_starting_position(&retreated));
+        assert_eq!(validate_board(&retreated), Err(BoardError::InvalidStartingPiece { row: 1, col: 7 }));
+    }
+
+    // `search_fixed_depth` with the pruning options of `config`
+    fn search_with_config(board: &Array2<i32>, player: i32, config: &SearchConfig) -> SearchResult {
+        let zobrist_table = ZobristTable::new_seeded(FIXED_DEPTH_ZOBRIST_SEED);
+        let mut transposition_table = new_transposition_table(config);
+        let evaluator = HandcraftedEvaluator { weights: Weights::default() };
+        let clock = SearchClock::new(Duration::MAX, None);
+        iterative_deepening(board, player, &evaluator, config, &zobrist_table, transposition_table.as_mut(), &[], 0, &clock, None)
+    }
+
+    // Total nodes over a few benchmark positions searched with `config`,
+    // and the best moves found
+    fn benchmark_nodes(config: &SearchConfig) -> (u64, u64, Vec<Option<Move>>) {
+        let (mut nodes, mut q_nodes, mut best_moves) = (0, 0, Vec::new());
+        for fen in default_benchmark_positions().into_iter().take(4) {
+            let (board, player) = parse_fen(fen).unwrap();
+            let result = search_with_config(&board, player, config);
+            nodes += result.nodes;
+            q_nodes += result.q_nodes;
+            best_moves.push(result.best_move);
+        }
+        (nodes, q_nodes, best_moves)
+    }
+
+    #[test]
+    fn futility_margin_prunes() {
+        assert!(futility_prunable(10.0, 20.0, 5.0));
+        assert!(!futility_prunable(10.0, 20.0, 15.0));
+
+        let config = SearchConfig { max_depth: 3, tt_size_mb: 1, ..SearchConfig::default() };
+        let (nodes, _, best_moves) = benchmark_nodes(&config);
+        // An infinite margin never prunes, so it searches the same tree as None
+        let wide = benchmark_nodes(&SearchConfig { futility_margin: Some(f64::INFINITY), ..config.clone() });
+        assert_eq!((wide.0, &wide.2), (nodes, &best_moves));
+        let zero = benchmark_nodes(&SearchConfig { futility_margin: Some(0.0), ..config.clone() });
+        assert!(zero.0 < nodes, "{} nodes with a zero margin, {} without pruning", zero.0, nodes);
+    }
+
+    #[test]
+    fn delta_margin_prunes() {
+        assert!(delta_prune(10.0, 20.0, 5.0));
+        assert!(!delta_prune(10.0, 20.0, f64::INFINITY));
+
+        let config = SearchConfig { max_depth: 3, tt_size_mb: 1, ..SearchConfig::default() };
+        let (_, q_nodes, best_moves) = benchmark_nodes(&config);
+        // An infinite gain never prunes, so it searches the same tree as None
+        let unbounded = benchmark_nodes(&SearchConfig { delta_margin: Some(f64::INFINITY), ..config.clone() });
+        assert_eq!((unbounded.1, &unbounded.2), (q_nodes, &best_moves));
+        let one_piece = benchmark_nodes(&SearchConfig { delta_margin: Some(Weights::default().piece_value), ..config.clone() });
+        assert!(one_piece.1 < q_nodes, "{} quiescence nodes with a one-piece margin, {} without pruning", one_piece.1, q_nodes);
+    }
+
+    #[test]
+    fn piece_counts() {
+        assert_eq!(count_pieces(&starting_board()), (PIECES_PER_SIDE, PIECES_PER_SIDE));
+        assert_eq!(piece_difference(&starting_board(), BLACK), 0);
+
+        let position = board([
+            "B...B....",
+            ".........",
+            "...B.....",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            "....W....",
+        ]);
+        assert_eq!(count_pieces(&position), (3, 1));
+        assert_eq!(piece_difference(&position, BLACK), 2);
+        assert_eq!(piece_difference(&position, WHITE), -2);
+        let bitboard = BitBoard::from_array2(&position);
+        assert_eq!((bitboard.count_pieces(BLACK), bitboard.count_pieces(WHITE)), (3, 1));
+    }
+
+    #[test]
+    fn weights_json_round_trip() {
+        let path = std::env::temp_dir().join(format!("fianco_weights_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut weights = Weights { piece_value: -3.5, repetition_penalty: 1e300, ..Weights::default() };
+        weights.to_json_file(path).unwrap();
+        let mut loaded = Weights::from_json_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+        let expected: Vec<f64> = weights.values_mut().into_iter().map(|value| *value).collect();
+        let actual: Vec<f64> = loaded.values_mut().into_iter().map(|value| *value).collect();
+        assert_eq!(actual, expected);
+
+        for value in [f64::INFINITY, f64::NAN] {
+            let error = Weights { mobility_value: value, ..Weights::default() }.to_json_file(path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert!(!std::path::Path::new(path).exists());
+        }
+    }
+}
@@ -0,0 +1,1876 @@
+// src/python.rs
+
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use numpy::{PyArray2, PyArray3, PyArray4, PyReadonlyArray2, PyReadwriteArray2, ToPyArray};
+use ndarray::Array2;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::engine::*;
+
+type PyMoveTuple = (i32, i32, i32, i32);
+
+// Searches with `config`, or `SearchConfig::default()` when it is None
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (board, player, weights, config=None, opening_book=None, info_fn=None, half_move_clock=0))]
+fn negamax(
+    _py: Python,
+    board: PyReadonlyArray2<i32>,
+    player: i32,
+    weights: &Bound<'_, PyAny>,
+    config: Option<PyRef<'_, PySearchConfig>>,
+    opening_book: Option<PyRef<'_, PyOpeningBook>>,
+    info_fn: Option<&Bound<'_, PyAny>>, // Called with search progress after each iteration
+    half_move_clock: u32, // Plies since the last capture or forward move
+) -> PyResult<PySearchResult> {
+    let board_array = board.as_array().to_owned();
+
+    let weights: Weights = weights.extract()?;
+
+    // Play straight from the opening book when the position is known
+    if let Some(opening_book) = opening_book {
+        let book = &opening_book.book;
+        if let Some(mv) = book.probe(&board_array) {
+            return Ok(book_move_result(&board_array, player, &weights, mv));
+        }
+    }
+
+    let config = config.map(|config| config.config.clone()).unwrap_or_default();
+    let evaluator = HandcraftedEvaluator { weights };
+    Ok(search_position(&board_array, player, &evaluator, &config, info_fn, half_move_clock))
+}
+
+// Like `negamax`, but picks among the book moves at random, weighted by how
+// often each was played, so that games vary. Searches on a book miss.
+#[pyfunction]
+#[pyo3(signature = (board, player, weights, book=None, config=None, info_fn=None, half_move_clock=0))]
+fn negamax_with_book(
+    board: PyReadonlyArray2<i32>,
+    player: i32,
+    weights: &Bound<'_, PyAny>,
+    book: Option<PyRef<'_, PyOpeningBook>>,
+    config: Option<PyRef<'_, PySearchConfig>>,
+    info_fn: Option<&Bound<'_, PyAny>>,
+    half_move_clock: u32,
+) -> PyResult<PySearchResult> {
+    let board_array = board.as_array().to_owned();
+    let weights: Weights = weights.extract()?;
+
+    if let Some(book) = book {
+        let book = &book.book;
+        if let Some(mv) = book.sample(&board_array, &mut rand::thread_rng()) {
+            return Ok(book_move_result(&board_array, player, &weights, mv));
+        }
+    }
+
+    let config = config.map(|config| config.config.clone()).unwrap_or_default();
+    let evaluator = HandcraftedEvaluator { weights };
+    Ok(search_position(&board_array, player, &evaluator, &config, info_fn, half_move_clock))
+}
+
+// Result for a move played from the book, with the static evaluation and no
+// search statistics
+fn book_move_result(board: &Array2<i32>, player: i32, weights: &Weights, mv: Move) -> PySearchResult {
+    let evaluation = evaluate_board(board, player, weights, true);
+    let pv = PrincipalVariation { moves: vec![mv] };
+    PySearchResult { result: SearchResult::new(Some(mv), evaluation, pv, 0) }
+}
+
+// One-off search with fresh tables, for the Python search functions
+fn search_position(
+    board_array: &Array2<i32>,
+    player: i32,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
+    info_fn: Option<&Bound<'_, PyAny>>,
+    half_move_clock: u32,
+) -> PySearchResult {
+    // Initialize Zobrist table
+    let zobrist_table = ZobristTable::new_random();
+
+    // Initialize transposition table
+    let mut transposition_table = new_transposition_table(config);
+
+    let search_callback = info_fn.map(|info_fn| py_search_callback(info_fn.clone().unbind()));
+    let clock = SearchClock::new(Duration::from_secs_f64(config.time_limit_secs), None)
+        .with_log(config.log_tree.as_deref());
+
+    let result = iterative_deepening(
+        board_array,
+        player,
+        evaluator,
+        config,
+        &zobrist_table,
+        transposition_table.as_mut(),
+        &[],
+        half_move_clock,
+        &clock,
+        search_callback.as_deref(),
+    );
+    PySearchResult { result }
+}
+
+// (best move, score, principal variation, nodes searched)
+type PyFixedDepthResult = (Option<PyMoveTuple>, f64, PyPrincipalVariation, u64);
+
+#[pyfunction]
+fn negamax_fixed_depth(
+    py: Python<'_>,
+    board: PyReadonlyArray2<i32>,
+    max_depth: i32,
+    player: i32,
+    weights: &Bound<'_, PyAny>,
+) -> PyResult<PyFixedDepthResult> {
+    let board_array = board.as_array().to_owned();
+    let weights: Weights = weights.extract()?;
+    let evaluator = HandcraftedEvaluator { weights };
+    let result = py.allow_threads(|| search_fixed_depth(&board_array, player, &evaluator, max_depth));
+    Ok((result.best_move.map(move_to_py), result.score, PyPrincipalVariation { pv: result.pv }, result.nodes))
+}
+
+fn move_to_py(mv: Move) -> PyMoveTuple {
+    (mv.from_row as i32, mv.from_col as i32, mv.to_row as i32, mv.to_col as i32)
+}
+
+fn move_from_py(mv: PyMoveTuple) -> Move {
+    let (fr, fc, tr, tc) = mv;
+    Move::new(fr as usize, fc as usize, tr as usize, tc as usize)
+}
+
+#[pyclass]
+#[derive(Clone)]
+struct PyMove {
+    mv: Move,
+}
+
+#[pymethods]
+impl PyMove {
+    #[new]
+    fn new(from_row: usize, from_col: usize, to_row: usize, to_col: usize) -> Self {
+        PyMove { mv: Move::new(from_row, from_col, to_row, to_col) }
+    }
+
+    #[getter]
+    fn get_from_row(&self) -> usize {
+        self.mv.from_row
+    }
+
+    #[getter]
+    fn get_from_col(&self) -> usize {
+        self.mv.from_col
+    }
+
+    #[getter]
+    fn get_to_row(&self) -> usize {
+        self.mv.to_row
+    }
+
+    #[getter]
+    fn get_to_col(&self) -> usize {
+        self.mv.to_col
+    }
+
+    fn to_tuple(&self) -> PyMoveTuple {
+        move_to_py(self.mv)
+    }
+
+    fn to_uci_string(&self) -> String {
+        self.mv.to_uci_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Move({})", self.mv.to_uci_string())
+    }
+}
+
+// Sequence of `PyMove`s; supports len(), indexing and iteration
+#[pyclass]
+struct PyPrincipalVariation {
+    pv: PrincipalVariation,
+}
+
+#[pymethods]
+impl PyPrincipalVariation {
+    fn to_uci_string(&self) -> String {
+        self.pv.to_uci_string()
+    }
+
+    fn __len__(&self) -> usize {
+        self.pv.len()
+    }
+
+    fn __getitem__(&self, index: isize) -> PyResult<PyMove> {
+        // Negative indices count from the end, as for lists
+        let resolved = if index < 0 { index + self.pv.len() as isize } else { index };
+        usize::try_from(resolved)
+            .ok()
+            .and_then(|i| self.pv.moves.get(i))
+            .map(|&mv| PyMove { mv })
+            .ok_or_else(|| PyIndexError::new_err("principal variation index out of range"))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PrincipalVariation({})", self.pv.to_uci_string())
+    }
+}
+
+// Result of a search, with the statistics as named attributes
+#[pyclass]
+struct PySearchResult {
+    result: SearchResult,
+}
+
+#[pymethods]
+impl PySearchResult {
+    #[getter]
+    fn best_move(&self) -> Option<PyMoveTuple> {
+        self.result.best_move.map(move_to_py)
+    }
+
+    #[getter]
+    fn score(&self) -> f64 {
+        self.result.score
+    }
+
+    #[getter]
+    fn pv(&self) -> PyPrincipalVariation {
+        PyPrincipalVariation { pv: self.result.pv.clone() }
+    }
+
+    #[getter]
+    fn depth_reached(&self) -> i32 {
+        self.result.depth_reached
+    }
+
+    #[getter]
+    fn nodes(&self) -> u64 {
+        self.result.nodes
+    }
+
+    #[getter]
+    fn q_nodes(&self) -> u64 {
+        self.result.q_nodes
+    }
+
+    #[getter]
+    fn time_ms(&self) -> u64 {
+        self.result.time_ms
+    }
+
+    #[getter]
+    fn tt_hits(&self) -> u64 {
+        self.result.tt_hits
+    }
+
+    #[getter]
+    fn tt_size(&self) -> usize {
+        self.result.tt_size
+    }
+
+    #[getter]
+    fn depth_stats(&self) -> Vec<PyDepthData> {
+        self.result.depth_stats.depth_data.iter().map(|data| PyDepthData { data: data.clone() }).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        let result = &self.result;
+        format!(
+            "SearchResult(best_move={}, score={}, pv={}, depth_reached={}, nodes={}, time_ms={}, tt_hits={}, tt_size={})",
+            result.best_move.map_or("None".to_string(), |mv| mv.to_uci_string()),
+            result.score,
+            result.pv.to_uci_string(),
+            result.depth_reached,
+            result.nodes,
+            result.time_ms,
+            result.tt_hits,
+            result.tt_size,
+        )
+    }
+}
+
+#[pyclass]
+struct PyDepthData {
+    data: DepthData,
+}
+
+#[pymethods]
+impl PyDepthData {
+    #[getter]
+    fn depth(&self) -> i32 {
+        self.data.depth
+    }
+
+    #[getter]
+    fn score(&self) -> f64 {
+        self.data.score
+    }
+
+    #[getter]
+    fn best_move(&self) -> PyMoveTuple {
+        move_to_py(self.data.best_move)
+    }
+
+    #[getter]
+    fn nodes(&self) -> u64 {
+        self.data.nodes
+    }
+
+    #[getter]
+    fn time_ms(&self) -> u64 {
+        self.data.time_ms
+    }
+
+    #[getter]
+    fn tt_hits(&self) -> u64 {
+        self.data.tt_hits
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DepthData(depth={}, score={}, best_move={}, nodes={}, time_ms={}, tt_hits={})",
+            self.data.depth,
+            self.data.score,
+            self.data.best_move.to_uci_string(),
+            self.data.nodes,
+            self.data.time_ms,
+            self.data.tt_hits,
+        )
+    }
+}
+
+// Wraps a Python callable as a `SearchCallback`. It is called with a dict
+// holding the `SearchInfo` fields, pv as a list of move tuples. Exceptions
+// are printed rather than aborting the search.
+fn py_search_callback(info_fn: PyObject) -> SearchCallback {
+    Box::new(move |info: SearchInfo| {
+        Python::with_gil(|py| {
+            let result = (|| {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("depth", info.depth)?;
+                dict.set_item("score", info.score)?;
+                dict.set_item("nodes", info.nodes)?;
+                dict.set_item("nps", info.nps)?;
+                dict.set_item("pv", info.pv.iter().map(|&mv| move_to_py(mv)).collect::<Vec<_>>())?;
+                dict.set_item("time_ms", info.time_ms)?;
+                info_fn.call1(py, (dict,))
+            })();
+            if let Err(err) = result {
+                err.print(py);
+            }
+        })
+    })
+}
+
+#[pyclass]
+struct PyTimeManager {
+    manager: TimeManager,
+}
+
+#[pymethods]
+impl PyTimeManager {
+    #[new]
+    #[pyo3(signature = (total_time_ms, increment_ms=0, moves_to_go=None, overhead_ms=0))]
+    fn new(total_time_ms: u64, increment_ms: u64, moves_to_go: Option<u32>, overhead_ms: u64) -> Self {
+        PyTimeManager {
+            manager: TimeManager { total_time_ms, increment_ms, moves_to_go, overhead_ms },
+        }
+    }
+
+    // Set the clock before each search
+    #[pyo3(signature = (total_time_ms, moves_to_go=None))]
+    fn update(&mut self, total_time_ms: u64, moves_to_go: Option<u32>) {
+        self.manager.total_time_ms = total_time_ms;
+        self.manager.moves_to_go = moves_to_go;
+    }
+
+    // Budget in seconds
+    fn time_for_move(&self, ply: u32) -> f64 {
+        self.manager.time_for_move(ply).as_secs_f64()
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+struct PySearchConfig {
+    config: SearchConfig,
+}
+
+#[pymethods]
+impl PySearchConfig {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (max_depth=DEFAULT_MAX_DEPTH, time_limit=1.0, soft_time_fraction=DEFAULT_SOFT_TIME_FRACTION, min_depth=DEFAULT_MIN_DEPTH, null_move=None, lmr=None, lmp_threshold=None, futility_margin=None, razoring_margin=None, delta_margin=None, aspiration_delta=None, use_history=true, use_counter_moves=false, use_gain_table=false, use_extensions=false, use_forced_capture_extension=false, use_threat_extension=false, log_tree=None, beam_width=None, parallel=false, tt_size_mb=DEFAULT_TT_SIZE_MB, threads=1, use_symmetry=false, lru_tt=false))]
+    fn new(
+        max_depth: i32,
+        time_limit: f64, // Seconds
+        soft_time_fraction: f64, // Share of time_limit after which no new iteration starts
+        min_depth: i32, // Depth always completed, even past time_limit
+        null_move: Option<NullMoveConfig>,
+        lmr: Option<LmrConfig>,
+        lmp_threshold: Option<f64>,
+        futility_margin: Option<f64>,
+        razoring_margin: Option<f64>, // Static evaluation deficit below alpha that razors depth 1-2 nodes
+        delta_margin: Option<f64>, // Usually the value of one piece
+        aspiration_delta: Option<f64>, // Half-width of the aspiration window
+        use_history: bool,
+        use_counter_moves: bool, // Order the reply that refuted the opponent's last move after killers
+        use_gain_table: bool, // Break quiet move ordering ties by average evaluation gain
+        use_extensions: bool, // Search forcing positions one ply deeper
+        use_forced_capture_extension: bool, // Search single-move positions one ply deeper
+        use_threat_extension: bool, // Search moves allowing a promotion one ply deeper
+        log_tree: Option<PathBuf>, // Newline-delimited JSON log of the searched tree
+        beam_width: Option<usize>, // Root moves kept for a beam search
+        parallel: bool, // Search root moves on multiple threads
+        tt_size_mb: usize,
+        threads: usize, // Lazy SMP search threads
+        use_symmetry: bool, // Share TT entries between mirror-image positions
+        lru_tt: bool, // Least recently used TT replacement
+    ) -> Self {
+        PySearchConfig {
+            config: SearchConfig {
+                max_depth,
+                time_limit_secs: time_limit,
+                soft_time_fraction,
+                min_depth,
+                null_move,
+                lmr,
+                lmp_threshold,
+                futility_margin,
+                razoring_margin,
+                delta_margin,
+                aspiration_delta,
+                use_history,
+                use_counter_moves,
+                use_gain_table,
+                use_extensions,
+                use_forced_capture_extension,
+                use_threat_extension,
+                log_tree,
+                beam_width,
+                parallel,
+                tt_size_mb,
+                threads,
+                use_symmetry,
+                lru_tt,
+            },
+        }
+    }
+
+    #[getter]
+    fn max_depth(&self) -> i32 {
+        self.config.max_depth
+    }
+
+    #[getter]
+    fn time_limit(&self) -> f64 {
+        self.config.time_limit_secs
+    }
+
+    #[getter]
+    fn soft_time_fraction(&self) -> f64 {
+        self.config.soft_time_fraction
+    }
+
+    #[getter]
+    fn min_depth(&self) -> i32 {
+        self.config.min_depth
+    }
+
+    #[getter]
+    fn lmp_threshold(&self) -> Option<f64> {
+        self.config.lmp_threshold
+    }
+
+    #[getter]
+    fn futility_margin(&self) -> Option<f64> {
+        self.config.futility_margin
+    }
+
+    #[getter]
+    fn razoring_margin(&self) -> Option<f64> {
+        self.config.razoring_margin
+    }
+
+    #[getter]
+    fn delta_margin(&self) -> Option<f64> {
+        self.config.delta_margin
+    }
+
+    #[getter]
+    fn aspiration_delta(&self) -> Option<f64> {
+        self.config.aspiration_delta
+    }
+
+    #[getter]
+    fn use_history(&self) -> bool {
+        self.config.use_history
+    }
+
+    #[getter]
+    fn use_counter_moves(&self) -> bool {
+        self.config.use_counter_moves
+    }
+
+    #[getter]
+    fn use_gain_table(&self) -> bool {
+        self.config.use_gain_table
+    }
+
+    #[getter]
+    fn use_extensions(&self) -> bool {
+        self.config.use_extensions
+    }
+
+    #[getter]
+    fn use_forced_capture_extension(&self) -> bool {
+        self.config.use_forced_capture_extension
+    }
+
+    #[getter]
+    fn use_threat_extension(&self) -> bool {
+        self.config.use_threat_extension
+    }
+
+    #[getter]
+    fn log_tree(&self) -> Option<PathBuf> {
+        self.config.log_tree.clone()
+    }
+
+    #[getter]
+    fn beam_width(&self) -> Option<usize> {
+        self.config.beam_width
+    }
+
+    #[getter]
+    fn parallel(&self) -> bool {
+        self.config.parallel
+    }
+
+    #[getter]
+    fn tt_size_mb(&self) -> usize {
+        self.config.tt_size_mb
+    }
+
+    #[getter]
+    fn threads(&self) -> usize {
+        self.config.threads
+    }
+
+    #[getter]
+    fn use_symmetry(&self) -> bool {
+        self.config.use_symmetry
+    }
+
+    #[getter]
+    fn lru_tt(&self) -> bool {
+        self.config.lru_tt
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.config)
+    }
+}
+
+#[pyclass]
+struct PyEngine {
+    engine: Engine,
+}
+
+#[pymethods]
+impl PyEngine {
+    #[new]
+    #[pyo3(signature = (weights, config=None, info_fn=None))]
+    fn new(
+        weights: &Bound<'_, PyAny>,
+        config: Option<PyRef<'_, PySearchConfig>>,
+        info_fn: Option<PyObject>,
+    ) -> PyResult<Self> {
+        let weights: Weights = weights.extract()?;
+        let config = config.map(|config| config.config.clone()).unwrap_or_default();
+        let mut engine = Engine::new(weights, config);
+        engine.set_search_callback(info_fn.map(py_search_callback));
+        Ok(PyEngine { engine })
+    }
+
+    // Plays the move on the numpy board in place and returns the captured piece
+    fn make_move(&mut self, mut board: PyReadwriteArray2<i32>, mv: PyMoveTuple) -> i32 {
+        let mut board_array = board.as_array().to_owned();
+        let captured_piece = self.engine.make_move(&mut board_array, move_from_py(mv));
+        board.as_array_mut().assign(&board_array);
+        captured_piece
+    }
+
+    // `time_limit` is either a PyTimeManager or a fixed number of seconds
+    fn best_move(
+        &mut self,
+        board: PyReadonlyArray2<i32>,
+        player: i32,
+        time_limit: &Bound<'_, PyAny>,
+    ) -> PyResult<PySearchResult> {
+        let time_manager = match time_limit.extract::<PyRef<'_, PyTimeManager>>() {
+            Ok(py_time_manager) => py_time_manager.manager.clone(),
+            Err(_) => TimeManager::fixed(Duration::from_secs_f64(time_limit.extract()?)),
+        };
+        let board_array = board.as_array().to_owned();
+        Ok(PySearchResult { result: self.engine.best_move(&board_array, player, &time_manager) })
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        self.engine.set_threads(threads);
+    }
+
+    #[pyo3(signature = (nn_eval=None))]
+    fn set_nn_eval(&mut self, nn_eval: Option<PyRef<'_, PyNnEval>>) {
+        self.engine.set_nn_eval(nn_eval.map(|nn_eval| nn_eval.nn_eval.clone()));
+    }
+
+    fn set_tt_size(&mut self, megabytes: usize) {
+        self.engine.set_tt_size(megabytes);
+    }
+
+    fn nodes_searched(&self) -> u64 {
+        self.engine.nodes_searched()
+    }
+
+    fn nps(&self) -> u64 {
+        self.engine.nps()
+    }
+
+    fn get_pv(&self) -> Vec<PyMoveTuple> {
+        self.engine.get_pv().into_iter().map(move_to_py).collect()
+    }
+
+    // Nodes logged by the last search as dicts with the keys depth, mv (a
+    // move tuple or None at the root), alpha, beta, eval and cutoff
+    fn last_search_log<'py>(&self, py: Python<'py>) -> PyResult<Option<Vec<Bound<'py, PyDict>>>> {
+        let Some(entries) = self.engine.last_search_log() else {
+            return Ok(None);
+        };
+        entries
+            .iter()
+            .map(|entry| {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("depth", entry.depth)?;
+                dict.set_item("mv", entry.mv.map(move_to_py))?;
+                dict.set_item("alpha", entry.alpha)?;
+                dict.set_item("beta", entry.beta)?;
+                dict.set_item("eval", entry.eval)?;
+                dict.set_item("cutoff", entry.cutoff)?;
+                Ok(dict)
+            })
+            .collect::<PyResult<_>>()
+            .map(Some)
+    }
+
+    // `info_fn` is called as in `negamax`; None removes it
+    #[pyo3(signature = (info_fn=None))]
+    fn set_info_fn(&mut self, info_fn: Option<PyObject>) {
+        self.engine.set_search_callback(info_fn.map(py_search_callback));
+    }
+
+    fn start_ponder(
+        &mut self,
+        board: PyReadonlyArray2<i32>,
+        our_move: PyMoveTuple,
+        expected_opponent_move: PyMoveTuple,
+    ) {
+        self.engine.start_ponder(
+            &board.as_array().to_owned(),
+            move_from_py(our_move),
+            move_from_py(expected_opponent_move),
+        );
+    }
+
+    // Returns the precomputed result on a ponder hit, else None
+    fn stop_ponder(&mut self, py: Python<'_>, opponent_move: PyMoveTuple) -> Option<PySearchResult> {
+        let opponent_move = move_from_py(opponent_move);
+        let result = py.allow_threads(|| self.engine.stop_ponder(opponent_move))?;
+        Some(PySearchResult { result })
+    }
+
+    fn push_position(&mut self, board: PyReadonlyArray2<i32>) {
+        self.engine.push_position(&board.as_array().to_owned());
+    }
+
+    fn repetition_count(&self, board: PyReadonlyArray2<i32>) -> usize {
+        let board_array = board.as_array().to_owned();
+        self.engine.repetition_count(self.engine.position_hash(&board_array))
+    }
+
+    fn is_repetition(&self, board: PyReadonlyArray2<i32>) -> bool {
+        let board_array = board.as_array().to_owned();
+        self.engine.is_repetition(self.engine.position_hash(&board_array))
+    }
+
+    fn set_half_move_clock(&mut self, half_move_clock: u32) {
+        self.engine.set_half_move_clock(half_move_clock);
+    }
+
+    fn claim_draw(&self) -> bool {
+        self.engine.claim_draw()
+    }
+
+    fn reset(&mut self) {
+        self.engine.reset();
+    }
+
+    // Raises IllegalMoveError unless `mv` is legal for `player`
+    fn annotate_move(
+        &mut self,
+        board: PyReadonlyArray2<i32>,
+        player: i32,
+        mv: PyMoveTuple,
+        depth: i32,
+    ) -> PyResult<PyMoveAnnotation> {
+        let board_array = board.as_array().to_owned();
+        let annotation = self.engine.annotate_move(&board_array, player, move_from_py(mv), depth)?;
+        Ok(PyMoveAnnotation { annotation })
+    }
+
+    #[pyo3(signature = (opening_book=None))]
+    fn set_opening_book(&mut self, opening_book: Option<PyRef<'_, PyOpeningBook>>) {
+        self.engine.set_opening_book(opening_book.map(|opening_book| opening_book.book.clone()));
+    }
+
+    fn set_blunder_threshold(&mut self, blunder_threshold: f64) {
+        self.engine.set_blunder_threshold(blunder_threshold);
+    }
+}
+
+#[pyclass]
+struct PyMoveAnnotation {
+    annotation: MoveAnnotation,
+}
+
+#[pymethods]
+impl PyMoveAnnotation {
+    #[getter]
+    fn mv(&self) -> PyMoveTuple {
+        move_to_py(self.annotation.mv)
+    }
+
+    #[getter]
+    fn score(&self) -> f64 {
+        self.annotation.score
+    }
+
+    #[getter]
+    fn best_response(&self) -> Option<PyMoveTuple> {
+        self.annotation.best_response.map(move_to_py)
+    }
+
+    #[getter]
+    fn is_blunder(&self) -> bool {
+        self.annotation.is_blunder
+    }
+
+    #[getter]
+    fn blunder_threshold(&self) -> f64 {
+        self.annotation.blunder_threshold
+    }
+
+    #[getter]
+    fn is_book(&self) -> bool {
+        self.annotation.is_book
+    }
+
+    #[getter]
+    fn pv_after(&self) -> Vec<PyMoveTuple> {
+        self.annotation.pv_after.iter().copied().map(move_to_py).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MoveAnnotation(mv={}, score={}, is_blunder={}, is_book={})",
+            self.annotation.mv.to_uci_string(),
+            self.annotation.score,
+            if self.annotation.is_blunder { "True" } else { "False" },
+            if self.annotation.is_book { "True" } else { "False" },
+        )
+    }
+}
+
+#[pyclass]
+struct PyOpeningBook {
+    book: OpeningBook,
+}
+
+#[pymethods]
+impl PyOpeningBook {
+    fn probe(&self, board: PyReadonlyArray2<i32>) -> Option<PyMoveTuple> {
+        let board_array = board.as_array().to_owned();
+        self.book.probe(&board_array).map(move_to_py)
+    }
+
+    fn __len__(&self) -> usize {
+        self.book.len()
+    }
+
+    fn save(&self, path: &str) -> PyResult<()> {
+        Ok(self.book.save(path)?)
+    }
+
+    fn to_pgn(&self) -> String {
+        opening_book_to_pgn(&self.book)
+    }
+}
+
+#[pyfunction]
+fn load_opening_book(path: &str) -> PyResult<PyOpeningBook> {
+    let book = OpeningBook::from_pgn_file(path)?;
+    Ok(PyOpeningBook { book })
+}
+
+// Load a book written by `PyOpeningBook.save`
+#[pyfunction]
+fn read_opening_book(path: &str) -> PyResult<PyOpeningBook> {
+    let book = OpeningBook::load(path)?;
+    Ok(PyOpeningBook { book })
+}
+
+// Book of the moves played in the first `max_ply` plies of `records`, kept if
+// played at least `min_frequency` times. Games that did not start from the
+// starting position are skipped.
+#[pyfunction]
+#[pyo3(name = "build_opening_book")]
+fn py_build_opening_book(records: Vec<PyRef<'_, PyGameRecord>>, max_ply: usize, min_frequency: usize) -> PyOpeningBook {
+    let start = compress_board(&starting_board());
+    let results: Vec<GameResult> = records
+        .iter()
+        .filter(|record| record.record.initial_board == start)
+        .map(|record| record.record.result.clone())
+        .collect();
+    PyOpeningBook { book: build_opening_book(&results, max_ply, min_frequency) }
+}
+
+impl From<ParseMoveError> for PyErr {
+    fn from(err: ParseMoveError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "parse_move")]
+fn py_parse_move(s: &str) -> PyResult<PyMoveTuple> {
+    Ok(move_to_py(parse_move(s)?))
+}
+
+#[pyfunction]
+#[pyo3(name = "format_move")]
+fn py_format_move(mv: PyMoveTuple) -> String {
+    format_move(move_from_py(mv))
+}
+
+#[pyfunction]
+#[pyo3(name = "starting_board")]
+fn py_starting_board(py: Python<'_>) -> Bound<'_, PyArray2<i32>> {
+    starting_board().to_pyarray_bound(py)
+}
+
+#[pyfunction]
+#[pyo3(name = "is_starting_position")]
+fn py_is_starting_position(board: PyReadonlyArray2<i32>) -> bool {
+    is_starting_position(&board.as_array().to_owned())
+}
+
+impl From<BoardError> for PyErr {
+    fn from(err: BoardError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+// Raises ValueError describing the first problem found
+#[pyfunction]
+#[pyo3(name = "validate_board")]
+fn py_validate_board(board: PyReadonlyArray2<i32>) -> PyResult<()> {
+    Ok(validate_board(&board.as_array().to_owned())?)
+}
+
+#[pyclass]
+struct PyBoardDiff {
+    diff: BoardDiff,
+}
+
+#[pymethods]
+impl PyBoardDiff {
+    #[getter(from_square)]
+    fn source_square(&self) -> (usize, usize) {
+        self.diff.from
+    }
+
+    #[getter]
+    fn to_square(&self) -> (usize, usize) {
+        self.diff.to
+    }
+
+    #[getter]
+    fn captured(&self) -> Option<(usize, usize)> {
+        self.diff.captured
+    }
+
+    #[getter]
+    fn piece(&self) -> i32 {
+        self.diff.piece
+    }
+
+    #[getter]
+    fn mv(&self) -> PyMoveTuple {
+        move_to_py(self.diff.to_move())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BoardDiff(mv={}, captured={}, piece={})",
+            self.diff.to_move().to_uci_string(),
+            self.diff.captured.map_or("None".to_string(), |(row, col)| square_name(row, col)),
+            self.diff.piece,
+        )
+    }
+}
+
+impl From<BoardDiffError> for PyErr {
+    fn from(err: BoardDiffError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+// Raises ValueError when the boards are not one move apart
+#[pyfunction]
+#[pyo3(name = "board_diff")]
+fn py_board_diff(before: PyReadonlyArray2<i32>, after: PyReadonlyArray2<i32>) -> PyResult<PyBoardDiff> {
+    let diff = board_diff(&before.as_array().to_owned(), &after.as_array().to_owned())?;
+    Ok(PyBoardDiff { diff })
+}
+
+impl From<FenError> for PyErr {
+    fn from(err: FenError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+// Returns the board and the player to move
+#[pyfunction]
+#[pyo3(name = "board_from_fen")]
+fn py_board_from_fen<'py>(py: Python<'py>, fen: &str) -> PyResult<(Bound<'py, PyArray2<i32>>, i32)> {
+    let (board, player) = parse_fen(fen)?;
+    Ok((board.to_pyarray_bound(py), player))
+}
+
+#[pyfunction]
+#[pyo3(name = "board_to_fen")]
+fn py_board_to_fen(board: PyReadonlyArray2<i32>, player: i32) -> String {
+    board_to_fen(&board.as_array().to_owned(), player)
+}
+
+impl From<IllegalMoveError> for PyErr {
+    fn from(err: IllegalMoveError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+// `board` as printed for a `GameState`, for use in place of the numpy repr
+#[pyfunction]
+fn display_board(board: PyReadonlyArray2<i32>) -> PyResult<String> {
+    let board_array = board.as_array().to_owned();
+    check_dimensions(&board_array)?;
+    Ok(format_board(&board_array))
+}
+
+#[pyclass]
+struct PyGameState {
+    state: GameState,
+}
+
+#[pymethods]
+impl PyGameState {
+    #[new]
+    fn new() -> Self {
+        PyGameState { state: GameState::new() }
+    }
+
+    #[staticmethod]
+    fn from_fen(fen: &str) -> PyResult<Self> {
+        Ok(PyGameState { state: GameState::from_fen(fen)? })
+    }
+
+    #[getter]
+    fn board<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<i32>> {
+        self.state.board.to_pyarray_bound(py)
+    }
+
+    #[getter]
+    fn player(&self) -> i32 {
+        self.state.player
+    }
+
+    #[getter]
+    fn move_history(&self) -> Vec<PyMoveTuple> {
+        self.state.move_history.iter().map(|&mv| move_to_py(mv)).collect()
+    }
+
+    #[getter]
+    fn half_move_clock(&self) -> u32 {
+        self.state.half_move_clock
+    }
+
+    fn is_fifty_move_draw(&self) -> bool {
+        self.state.is_fifty_move_draw()
+    }
+
+    fn legal_moves(&self) -> Vec<PyMoveTuple> {
+        self.state.legal_moves().into_iter().map(move_to_py).collect()
+    }
+
+    fn apply_move(&mut self, mv: PyMoveTuple) -> PyResult<i32> {
+        Ok(self.state.apply_move(move_from_py(mv))?)
+    }
+
+    fn undo_move(&mut self) -> Option<PyMoveTuple> {
+        self.state.undo_move().map(move_to_py)
+    }
+
+    fn winner(&self) -> Option<i32> {
+        self.state.winner()
+    }
+
+    fn is_draw(&self) -> bool {
+        self.state.is_draw()
+    }
+
+    fn to_fen(&self) -> String {
+        self.state.to_fen()
+    }
+
+    fn __str__(&self) -> String {
+        self.state.to_string()
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "compress_board")]
+fn py_compress_board<'py>(py: Python<'py>, board: PyReadonlyArray2<i32>) -> Bound<'py, PyBytes> {
+    PyBytes::new_bound(py, &compress_board(&board.as_array().to_owned()))
+}
+
+#[pyfunction]
+#[pyo3(name = "decompress_board")]
+fn py_decompress_board<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyArray2<i32>>> {
+    let data: &[u8; COMPRESSED_BOARD_SIZE] = data.try_into().map_err(|_| {
+        PyValueError::new_err(format!("compressed board must be {} bytes", COMPRESSED_BOARD_SIZE))
+    })?;
+    Ok(decompress_board(data).to_pyarray_bound(py))
+}
+
+// Float32 array of shape (3, 9, 9), see `position_planes`
+#[pyfunction]
+fn position_to_tensor<'py>(
+    py: Python<'py>,
+    board: PyReadonlyArray2<i32>,
+    player: i32,
+) -> PyResult<Bound<'py, PyArray3<f32>>> {
+    let board_array = board.as_array().to_owned();
+    check_dimensions(&board_array)?;
+    Ok(position_planes(&board_array, player).to_pyarray_bound(py))
+}
+
+// Float32 array of shape (N, 3, 9, 9) for a list of (board, player) pairs
+#[pyfunction]
+#[pyo3(name = "batch_positions_to_tensor")]
+fn py_batch_positions_to_tensor<'py>(
+    py: Python<'py>,
+    positions: Vec<(PyReadonlyArray2<'py, i32>, i32)>,
+) -> PyResult<Bound<'py, PyArray4<f32>>> {
+    let mut boards = Vec::with_capacity(positions.len());
+    for (board, player) in positions {
+        let board_array = board.as_array().to_owned();
+        check_dimensions(&board_array)?;
+        boards.push((board_array, player));
+    }
+    Ok(batch_positions_to_tensor(&boards).to_pyarray_bound(py))
+}
+
+// (from, to) logits
+type PyPolicyLogits<'py> = (Bound<'py, PyArray2<f32>>, Bound<'py, PyArray2<f32>>);
+
+// (from, to) float32 arrays of shape (9, 9), see `policy_logits`
+#[pyfunction]
+fn get_policy_logits<'py>(
+    py: Python<'py>,
+    board: PyReadonlyArray2<i32>,
+    player: i32,
+    depth: i32,
+    weights: &Bound<'_, PyAny>,
+) -> PyResult<PyPolicyLogits<'py>> {
+    let board_array = board.as_array().to_owned();
+    check_dimensions(&board_array)?;
+    let evaluator = HandcraftedEvaluator { weights: weights.extract()? };
+    let (from_logits, to_logits) = py.allow_threads(|| policy_logits(&board_array, player, &evaluator, depth));
+    Ok((from_logits.to_pyarray_bound(py), to_logits.to_pyarray_bound(py)))
+}
+
+// Evaluations of `boards[i]` for `players[i]`
+#[pyfunction]
+#[pyo3(name = "batch_evaluate")]
+fn py_batch_evaluate(
+    py: Python<'_>,
+    boards: Vec<PyReadonlyArray2<'_, i32>>,
+    players: Vec<i32>,
+    weights: &Bound<'_, PyAny>,
+) -> PyResult<Vec<f64>> {
+    if boards.len() != players.len() {
+        return Err(PyValueError::new_err(format!(
+            "got {} boards but {} players",
+            boards.len(),
+            players.len()
+        )));
+    }
+    let weights: Weights = weights.extract()?;
+    let mut positions = Vec::with_capacity(boards.len());
+    for (board, player) in boards.iter().zip(players) {
+        let board_array = board.as_array().to_owned();
+        check_dimensions(&board_array)?;
+        positions.push((board_array, player));
+    }
+    Ok(py.allow_threads(|| batch_evaluate(&positions, &weights)))
+}
+
+// Keys for `hash_board`, drawn once per process so that hashes stay
+// comparable between calls
+static PY_ZOBRIST_TABLE: OnceLock<ZobristTable> = OnceLock::new();
+
+// Zobrist hash of the board, as the engine computes it, for position caches
+// kept on the Python side. Keys are random per process.
+#[pyfunction]
+fn hash_board(board: PyReadonlyArray2<i32>) -> u64 {
+    let zobrist_table = PY_ZOBRIST_TABLE.get_or_init(ZobristTable::new_random);
+    zobrist_table.compute_hash(&board.as_array().to_owned())
+}
+
+// Same as `hash_board` with keys drawn from `seed`, so hashes are
+// reproducible across runs
+#[pyfunction]
+fn hash_board_with_seed(board: PyReadonlyArray2<i32>, seed: u64) -> u64 {
+    ZobristTable::new_seeded(seed).compute_hash(&board.as_array().to_owned())
+}
+
+// Zobrist keys usable from Python, e.g. to hash positions the same way in
+// several processes
+#[pyclass]
+struct PyZobristTable {
+    zobrist_table: ZobristTable,
+}
+
+#[pymethods]
+impl PyZobristTable {
+    #[staticmethod]
+    fn new_random() -> Self {
+        PyZobristTable { zobrist_table: ZobristTable::new_random() }
+    }
+
+    #[staticmethod]
+    fn new_seeded(seed: u64) -> Self {
+        PyZobristTable { zobrist_table: ZobristTable::new_seeded(seed) }
+    }
+
+    fn compute_hash(&self, board: PyReadonlyArray2<i32>) -> u64 {
+        self.zobrist_table.compute_hash(&board.as_array().to_owned())
+    }
+}
+
+// Weights are read from the attributes named in `Weights::NAMES`, plus an
+// optional `pst` attribute holding a PyPieceSquareTable or None
+impl<'py> FromPyObject<'py> for Weights {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let mut weights = Weights::default();
+        for (name, value) in Weights::NAMES.iter().zip(weights.values_mut()) {
+            *value = ob.getattr(*name)?.extract()?;
+        }
+        if ob.hasattr("pst")? {
+            let pst: Option<PyRef<'_, PyPieceSquareTable>> = ob.getattr("pst")?.extract()?;
+            weights.pst = pst.map(|pst| pst.pst.clone());
+        }
+        Ok(weights)
+    }
+}
+
+#[pyclass]
+struct PyPieceSquareTable {
+    pst: PieceSquareTable,
+}
+
+impl PyPieceSquareTable {
+    fn check_square(row: usize, col: usize) -> PyResult<()> {
+        if row >= BOARD_SIZE || col >= BOARD_SIZE {
+            return Err(PyIndexError::new_err("square out of range"));
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PyPieceSquareTable {
+    // `values` is a 9x9 array whose row 0 is the owner's back row
+    #[new]
+    fn new(values: PyReadonlyArray2<f64>) -> PyResult<Self> {
+        let values = values.as_array();
+        if values.dim() != (BOARD_SIZE, BOARD_SIZE) {
+            return Err(PyValueError::new_err(format!("table must be {}x{}", BOARD_SIZE, BOARD_SIZE)));
+        }
+        let mut table = [[0.0; BOARD_SIZE]; BOARD_SIZE];
+        for ((row, col), &value) in values.indexed_iter() {
+            table[row][col] = value;
+        }
+        Ok(PyPieceSquareTable { pst: PieceSquareTable { values: table } })
+    }
+
+    // The table equivalent to the advancement and edge terms of `weights`
+    #[staticmethod]
+    fn from_weights(weights: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let weights: Weights = weights.extract()?;
+        Ok(PyPieceSquareTable { pst: PieceSquareTable::from_weights(&weights) })
+    }
+
+    #[getter]
+    fn values<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        Array2::from_shape_fn((BOARD_SIZE, BOARD_SIZE), |(row, col)| self.pst.values[row][col]).to_pyarray_bound(py)
+    }
+
+    fn __getitem__(&self, square: (usize, usize)) -> PyResult<f64> {
+        let (row, col) = square;
+        Self::check_square(row, col)?;
+        Ok(self.pst.values[row][col])
+    }
+
+    fn __setitem__(&mut self, square: (usize, usize), value: f64) -> PyResult<()> {
+        let (row, col) = square;
+        Self::check_square(row, col)?;
+        self.pst.values[row][col] = value;
+        Ok(())
+    }
+
+    fn eval(&self, board: PyReadonlyArray2<i32>, player: i32) -> f64 {
+        self.pst.eval(&board.as_array().to_owned(), player)
+    }
+}
+
+// Exchange result of the capture `mv` in pieces, see `see`. Raises
+// IllegalMoveError unless `mv` is a legal capture.
+#[pyfunction]
+fn static_exchange_evaluation(board: PyReadonlyArray2<i32>, mv: PyMoveTuple, player: i32) -> PyResult<i32> {
+    let board_array = board.as_array().to_owned();
+    let mv = move_from_py(mv);
+    if !is_legal_move(&board_array, mv, player) || mv.from_row.abs_diff(mv.to_row) != 2 {
+        return Err(IllegalMoveError(mv).into());
+    }
+    Ok(see(&BitBoard::from_array2(&board_array), &mv, player))
+}
+
+impl From<NnError> for PyErr {
+    fn from(err: NnError) -> PyErr {
+        match err {
+            NnError::Io(err) => err.into(),
+            err => PyValueError::new_err(err.to_string()),
+        }
+    }
+}
+
+#[pyclass]
+struct PyNnEval {
+    nn_eval: Arc<NnEval>,
+}
+
+#[pymethods]
+impl PyNnEval {
+    #[staticmethod]
+    fn from_file(path: &str) -> PyResult<Self> {
+        Ok(PyNnEval { nn_eval: Arc::new(NnEval::from_file(path)?) })
+    }
+
+    fn eval(&self, board: PyReadonlyArray2<i32>, player: i32) -> f64 {
+        self.nn_eval.eval(&board.as_array().to_owned(), player)
+    }
+}
+
+// (row, col) of every piece `player` can capture
+#[pyfunction]
+#[pyo3(name = "get_capture_targets")]
+fn py_get_capture_targets(board: PyReadonlyArray2<i32>, player: i32) -> HashSet<(usize, usize)> {
+    get_capture_targets(&board.as_array().to_owned(), player)
+}
+
+// Legal moves for `player`; only captures when one is available
+#[pyfunction]
+#[pyo3(name = "get_valid_moves")]
+fn py_get_valid_moves(board: PyReadonlyArray2<i32>, player: i32) -> Vec<PyMoveTuple> {
+    get_valid_moves(&board.as_array().to_owned(), player)
+        .into_iter()
+        .map(move_to_py)
+        .collect()
+}
+
+#[pyfunction]
+#[pyo3(name = "get_winner")]
+fn py_get_winner(board: PyReadonlyArray2<i32>) -> Option<i32> {
+    get_winner(&board.as_array().to_owned())
+}
+
+// Returns ("ongoing" | "black_won" | "white_won" | "stalemate", player),
+// where player is the stalemated side and None otherwise
+#[pyfunction]
+#[pyo3(name = "game_status")]
+fn py_game_status(board: PyReadonlyArray2<i32>, player: i32) -> (&'static str, Option<i32>) {
+    match game_status(&board.as_array().to_owned(), player) {
+        GameStatus::Ongoing => ("ongoing", None),
+        GameStatus::BlackWon => ("black_won", None),
+        GameStatus::WhiteWon => ("white_won", None),
+        GameStatus::Stalemate(player) => ("stalemate", Some(player)),
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "count_pieces")]
+fn py_count_pieces(board: PyReadonlyArray2<i32>) -> (usize, usize) {
+    count_pieces(&board.as_array().to_owned())
+}
+
+#[pyfunction]
+#[pyo3(name = "piece_difference")]
+fn py_piece_difference(board: PyReadonlyArray2<i32>, player: i32) -> i32 {
+    piece_difference(&board.as_array().to_owned(), player)
+}
+
+// Returns a copy of `board` with the move played, leaving `board` untouched
+#[pyfunction]
+#[pyo3(name = "make_move")]
+fn py_make_move<'py>(
+    py: Python<'py>,
+    board: PyReadonlyArray2<i32>,
+    mv: PyMoveTuple,
+    player: i32,
+) -> PyResult<Bound<'py, PyArray2<i32>>> {
+    let mut board_array = board.as_array().to_owned();
+    let mv = move_from_py(mv);
+    if !is_legal_move(&board_array, mv, player) {
+        return Err(IllegalMoveError(mv).into());
+    }
+    make_move(&mut board_array, &mv, player, &mut 0, &NO_ZOBRIST_KEYS);
+    Ok(board_array.to_pyarray_bound(py))
+}
+
+#[pyfunction]
+#[pyo3(name = "is_legal_move")]
+fn py_is_legal_move(board: PyReadonlyArray2<i32>, mv: PyMoveTuple, player: i32) -> bool {
+    is_legal_move(&board.as_array().to_owned(), move_from_py(mv), player)
+}
+
+// Returns the total leaf count and the count below each root move
+#[pyfunction]
+#[pyo3(name = "perft")]
+fn py_perft(board: PyReadonlyArray2<i32>, depth: usize, player: i32) -> PyResult<(u64, HashMap<String, u64>)> {
+    let board_array = board.as_array().to_owned();
+    if depth == 0 {
+        return Ok((1, HashMap::new()));
+    }
+
+    let divide = perft_divide(&board_array, depth, player);
+    let total = divide.iter().map(|(_, nodes)| nodes).sum();
+    let per_move = divide
+        .into_iter()
+        .map(|(mv, nodes)| (perft_move_string(&mv), nodes))
+        .collect();
+    Ok((total, per_move))
+}
+
+#[pyfunction]
+#[pyo3(name = "perft_threaded")]
+fn py_perft_threaded(py: Python<'_>, board: PyReadonlyArray2<i32>, depth: usize, player: i32) -> u64 {
+    let board_array = board.as_array().to_owned();
+    py.allow_threads(|| perft_threaded(&board_array, depth, player))
+}
+
+// Prints the count below each root move and returns the total
+#[pyfunction]
+#[pyo3(name = "perft_divide")]
+fn py_perft_divide(board: PyReadonlyArray2<i32>, depth: usize, player: i32) -> PyResult<u64> {
+    let board_array = board.as_array().to_owned();
+    let mut total = 0;
+    for (mv, nodes) in perft_divide(&board_array, depth, player) {
+        println!("{}: {}", perft_move_string(&mv), nodes);
+        total += nodes;
+    }
+    println!("Total: {}", total);
+    Ok(total)
+}
+
+#[pyclass]
+struct PyBenchmarkResult {
+    result: BenchmarkResult,
+}
+
+#[pymethods]
+impl PyBenchmarkResult {
+    #[getter]
+    fn total_nodes(&self) -> u64 {
+        self.result.total_nodes
+    }
+
+    #[getter]
+    fn total_time_ms(&self) -> u64 {
+        self.result.total_time_ms
+    }
+
+    #[getter]
+    fn nps(&self) -> u64 {
+        self.result.nps
+    }
+
+    // (FEN, nodes, milliseconds) for each position
+    #[getter]
+    fn results(&self) -> Vec<(String, u64, u64)> {
+        self.result.results.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BenchmarkResult(positions={}, total_nodes={}, total_time_ms={}, nps={})",
+            self.result.results.len(),
+            self.result.total_nodes,
+            self.result.total_time_ms,
+            self.result.nps,
+        )
+    }
+}
+
+// Runs `default_benchmark_positions` when `positions` is None
+#[pyfunction]
+#[pyo3(name = "benchmark", signature = (positions=None, depth=6))]
+fn py_benchmark(py: Python<'_>, positions: Option<Vec<String>>, depth: i32) -> PyResult<PyBenchmarkResult> {
+    let positions = positions
+        .unwrap_or_else(|| default_benchmark_positions().into_iter().map(String::from).collect());
+    let result = py.allow_threads(|| benchmark(&positions, depth))?;
+    Ok(PyBenchmarkResult { result })
+}
+
+// (winner, moves, final_board, ply_count)
+type PyGameResult<'py> = (Option<i32>, Vec<PyMoveTuple>, Bound<'py, PyArray2<i32>>, usize);
+
+// Plays a game between two Python callables taking (board, player) and
+// returning a move tuple
+#[pyfunction]
+#[pyo3(name = "play_game")]
+fn py_play_game<'py>(
+    py: Python<'py>,
+    black_engine: &Bound<'py, PyAny>,
+    white_engine: &Bound<'py, PyAny>,
+    max_moves: usize,
+) -> PyResult<PyGameResult<'py>> {
+    // The first Python error ends the game and is re-raised afterwards
+    let error: RefCell<Option<PyErr>> = RefCell::new(None);
+    let call_engine = |engine: &Bound<'py, PyAny>, board: &Array2<i32>, player: i32| -> Move {
+        if error.borrow().is_some() {
+            return ILLEGAL_MOVE;
+        }
+        match engine
+            .call1((board.to_pyarray_bound(py), player))
+            .and_then(|mv| mv.extract::<PyMoveTuple>())
+        {
+            Ok(mv) => move_from_py(mv),
+            Err(err) => {
+                *error.borrow_mut() = Some(err);
+                ILLEGAL_MOVE
+            }
+        }
+    };
+
+    let result = play_game(
+        |board, player| call_engine(black_engine, board, player),
+        |board, player| call_engine(white_engine, board, player),
+        max_moves,
+    );
+    if let Some(err) = error.into_inner() {
+        return Err(err);
+    }
+
+    Ok((
+        result.winner,
+        result.moves.into_iter().map(move_to_py).collect(),
+        result.final_board.to_pyarray_bound(py),
+        result.ply_count,
+    ))
+}
+
+impl From<PgnError> for PyErr {
+    fn from(err: PgnError) -> PyErr {
+        match err {
+            PgnError::Io(err) => err.into(),
+            PgnError::IllegalMove(err) => err.into(),
+            err => PyValueError::new_err(err.to_string()),
+        }
+    }
+}
+
+#[pyclass]
+struct PyGameRecord {
+    record: GameRecord,
+}
+
+#[pymethods]
+impl PyGameRecord {
+    // Replays `moves` from `initial_board` (the starting position by
+    // default), White moving first. `winner` is None for a draw.
+    #[new]
+    #[pyo3(signature = (moves, winner, date="", black_engine="", white_engine="", initial_board=None))]
+    fn new(
+        moves: Vec<PyMoveTuple>,
+        winner: Option<i32>,
+        date: &str,
+        black_engine: &str,
+        white_engine: &str,
+        initial_board: Option<PyReadonlyArray2<i32>>,
+    ) -> PyResult<Self> {
+        let initial_board = match initial_board {
+            Some(board) => board.as_array().to_owned(),
+            None => starting_board(),
+        };
+        check_dimensions(&initial_board).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        let moves: Vec<Move> = moves.into_iter().map(move_from_py).collect();
+        let final_board = replay_game(&initial_board, &moves)?;
+        let result = GameResult { winner, ply_count: moves.len(), moves, final_board };
+        Ok(PyGameRecord { record: GameRecord::new(&initial_board, result, date, black_engine, white_engine) })
+    }
+
+    #[staticmethod]
+    fn from_pgn_string(s: &str) -> PyResult<Self> {
+        Ok(PyGameRecord { record: GameRecord::from_pgn_string(s)? })
+    }
+
+    fn to_pgn_string(&self) -> String {
+        self.record.to_pgn_string()
+    }
+
+    #[getter]
+    fn moves(&self) -> Vec<PyMoveTuple> {
+        self.record.moves.iter().copied().map(move_to_py).collect()
+    }
+
+    #[getter]
+    fn winner(&self) -> Option<i32> {
+        self.record.result.winner
+    }
+
+    #[getter]
+    fn date(&self) -> &str {
+        &self.record.date
+    }
+
+    #[getter]
+    fn black_engine(&self) -> &str {
+        &self.record.black_engine
+    }
+
+    #[getter]
+    fn white_engine(&self) -> &str {
+        &self.record.white_engine
+    }
+
+    #[getter]
+    fn initial_board<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<i32>> {
+        decompress_board(&self.record.initial_board).to_pyarray_bound(py)
+    }
+
+    #[getter]
+    fn final_board<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<i32>> {
+        self.record.result.final_board.to_pyarray_bound(py)
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "write_pgn_file")]
+fn py_write_pgn_file(records: Vec<PyRef<'_, PyGameRecord>>, path: &str) -> PyResult<()> {
+    let records: Vec<GameRecord> = records.iter().map(|record| record.record.clone()).collect();
+    Ok(write_pgn_file(&records, path)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "read_pgn_file")]
+fn py_read_pgn_file(path: &str) -> PyResult<Vec<PyGameRecord>> {
+    let records = read_pgn_file(path)?;
+    Ok(records.into_iter().map(|record| PyGameRecord { record }).collect())
+}
+
+#[pyfunction]
+#[pyo3(name = "generate_self_play_games")]
+fn py_generate_self_play_games(
+    py: Python<'_>,
+    n_games: usize,
+    depth: i32,
+    weights: &Bound<'_, PyAny>,
+    output_path: &str,
+) -> PyResult<usize> {
+    let weights: Weights = weights.extract()?;
+    let records = py.allow_threads(|| generate_self_play_games(n_games, depth, &weights, output_path))?;
+    Ok(records)
+}
+
+#[pyclass]
+struct PyTournamentResult {
+    result: TournamentResult,
+}
+
+#[pymethods]
+impl PyTournamentResult {
+    #[getter]
+    fn wins_a(&self) -> u32 {
+        self.result.wins_a
+    }
+
+    #[getter]
+    fn wins_b(&self) -> u32 {
+        self.result.wins_b
+    }
+
+    #[getter]
+    fn draws(&self) -> u32 {
+        self.result.draws
+    }
+
+    #[getter]
+    fn elo_diff(&self) -> f64 {
+        self.result.elo_diff
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TournamentResult(wins_a={}, wins_b={}, draws={}, elo_diff={:.1})",
+            self.result.wins_a, self.result.wins_b, self.result.draws, self.result.elo_diff,
+        )
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "run_tournament")]
+fn py_run_tournament(
+    py: Python<'_>,
+    engine_a_config: PyRef<'_, PySearchConfig>,
+    engine_b_config: PyRef<'_, PySearchConfig>,
+    n_games: usize,
+    weights: &Bound<'_, PyAny>,
+) -> PyResult<PyTournamentResult> {
+    let weights: Weights = weights.extract()?;
+    let (config_a, config_b) = (engine_a_config.config.clone(), engine_b_config.config.clone());
+    let result = py.allow_threads(|| run_tournament(&config_a, &config_b, n_games, &weights));
+    Ok(PyTournamentResult { result })
+}
+
+type PyWeightedMove = (PyMoveTuple, f64);
+
+// Returns the sampled move and the score of every root move
+#[pyfunction]
+#[pyo3(signature = (board, player, weights, depth, temperature, time_limit=1.0))]
+fn stochastic_best_move(
+    py: Python<'_>,
+    board: PyReadonlyArray2<i32>,
+    player: i32,
+    weights: &Bound<'_, PyAny>,
+    depth: i32,
+    temperature: f64,
+    time_limit: f64,
+) -> PyResult<(Option<PyMoveTuple>, Vec<PyWeightedMove>)> {
+    let board_array = board.as_array().to_owned();
+    let weights: Weights = weights.extract()?;
+    let config = SearchConfig { max_depth: depth, time_limit_secs: time_limit, ..SearchConfig::default() };
+
+    let evaluator = HandcraftedEvaluator { weights };
+    let scores = py.allow_threads(|| root_move_scores(&board_array, player, &evaluator, &config));
+    let (moves, values): (Vec<Move>, Vec<f64>) = scores.iter().copied().unzip();
+    let sampled = sample_move(&moves, &values, temperature).map(move_to_py);
+    Ok((sampled, scores.into_iter().map(|(mv, score)| (move_to_py(mv), score)).collect()))
+}
+
+#[pyclass]
+struct PyGameTreeNode {
+    node: GameTreeNode,
+}
+
+#[pymethods]
+impl PyGameTreeNode {
+    #[new]
+    fn new(board: PyReadonlyArray2<i32>, player: i32) -> Self {
+        PyGameTreeNode { node: GameTreeNode::new(board.as_array().to_owned(), player) }
+    }
+
+    fn expand(&mut self, engine: PyRef<'_, PyEngine>, depth: i32) {
+        self.node.expand(&engine.engine, depth);
+    }
+
+    fn best_line(&self) -> Vec<PyMoveTuple> {
+        self.node.best_line().into_iter().map(move_to_py).collect()
+    }
+
+    fn to_pgn(&self) -> String {
+        self.node.to_pgn()
+    }
+
+    #[getter]
+    fn board<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<i32>> {
+        self.node.board.to_pyarray_bound(py)
+    }
+
+    #[getter]
+    fn player(&self) -> i32 {
+        self.node.player
+    }
+
+    #[getter]
+    fn mv_from_parent(&self) -> Option<PyMoveTuple> {
+        self.node.mv_from_parent.map(move_to_py)
+    }
+
+    #[getter]
+    fn eval(&self) -> f64 {
+        self.node.eval
+    }
+
+    #[getter]
+    fn depth(&self) -> i32 {
+        self.node.depth
+    }
+
+    // Copies of the children; expanding them does not change this tree
+    #[getter]
+    fn children(&self) -> Vec<PyGameTreeNode> {
+        self.node.children.iter().map(|node| PyGameTreeNode { node: node.clone() }).collect()
+    }
+}
+
+// Returns the tuned weights as a dict of field name to value
+#[pyfunction]
+#[pyo3(name = "texel_tune")]
+fn py_texel_tune(
+    py: Python<'_>,
+    positions: Vec<(PyReadonlyArray2<i32>, i32, f64)>,
+    initial_weights: &Bound<'_, PyAny>,
+    iterations: usize,
+) -> PyResult<HashMap<String, f64>> {
+    let initial_weights: Weights = initial_weights.extract()?;
+    let positions: Vec<(Array2<i32>, i32, f64)> = positions
+        .iter()
+        .map(|(board, player, result)| (board.as_array().to_owned(), *player, *result))
+        .collect();
+
+    let mut weights = py.allow_threads(|| texel_tune(&positions, initial_weights, iterations));
+    Ok(Weights::NAMES
+        .iter()
+        .zip(weights.values_mut())
+        .map(|(name, value)| (name.to_string(), *value))
+        .collect())
+}
+
+// Runs `mcts_search` for up to `time_limit` seconds and returns the most
+// visited move and its win rate for `player`
+#[pyfunction]
+fn mcts(
+    board: PyReadonlyArray2<i32>,
+    player: i32,
+    time_limit: f64,
+    simulations: u32,
+) -> PyResult<(Option<PyMoveTuple>, f64)> {
+    let board_array = board.as_array().to_owned();
+    Ok(match mcts_search(&board_array, player, Duration::from_secs_f64(time_limit), simulations) {
+        Some((mv, win_rate)) => (Some(move_to_py(mv)), win_rate),
+        None => (None, 0.0),
+    })
+}
+
+// `mcts` with playouts biased towards moves the evaluation likes, sampled
+// with softmax at `temperature`
+#[pyfunction]
+fn mcts_biased(
+    py: Python<'_>,
+    board: PyReadonlyArray2<i32>,
+    player: i32,
+    weights: &Bound<'_, PyAny>,
+    time_limit: f64,
+    temperature: f64,
+) -> PyResult<Option<PyMoveTuple>> {
+    let board_array = board.as_array().to_owned();
+    let policy = BiasedRolloutPolicy { weights: weights.extract()?, temperature };
+    let result = py.allow_threads(|| {
+        mcts_search_with_policy(&board_array, player, Duration::from_secs_f64(time_limit), u32::MAX, &policy)
+    });
+    Ok(result.map(|(mv, _)| move_to_py(mv)))
+}
+
+#[pymodule]
+fn fianco_ai(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(negamax, m)?)?;
+    m.add_function(wrap_pyfunction!(negamax_fixed_depth, m)?)?;
+    m.add_function(wrap_pyfunction!(negamax_with_book, m)?)?;
+    m.add_class::<PyEngine>()?;
+    m.add_class::<PyOpeningBook>()?;
+    m.add_class::<PyMove>()?;
+    m.add_class::<PyTimeManager>()?;
+    m.add_class::<PyGameState>()?;
+    m.add_class::<PyPrincipalVariation>()?;
+    m.add_class::<PySearchResult>()?;
+    m.add_class::<PyNnEval>()?;
+    m.add_class::<PyBenchmarkResult>()?;
+    m.add_class::<PyGameTreeNode>()?;
+    m.add_class::<PyMoveAnnotation>()?;
+    m.add_class::<PyBoardDiff>()?;
+    m.add_class::<PyDepthData>()?;
+    m.add_class::<PyTournamentResult>()?;
+    m.add_class::<PyGameRecord>()?;
+    m.add_class::<PyZobristTable>()?;
+    m.add_class::<PyPieceSquareTable>()?;
+    m.add_class::<PySearchConfig>()?;
+    m.add_function(wrap_pyfunction!(load_opening_book, m)?)?;
+    m.add_function(wrap_pyfunction!(read_opening_book, m)?)?;
+    m.add_function(wrap_pyfunction!(py_build_opening_book, m)?)?;
+    m.add_function(wrap_pyfunction!(py_perft, m)?)?;
+    m.add_function(wrap_pyfunction!(py_perft_divide, m)?)?;
+    m.add_function(wrap_pyfunction!(py_benchmark, m)?)?;
+    m.add_function(wrap_pyfunction!(py_perft_threaded, m)?)?;
+    m.add_function(wrap_pyfunction!(py_play_game, m)?)?;
+    m.add_function(wrap_pyfunction!(py_write_pgn_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_read_pgn_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_generate_self_play_games, m)?)?;
+    m.add_function(wrap_pyfunction!(py_run_tournament, m)?)?;
+    m.add_function(wrap_pyfunction!(py_compress_board, m)?)?;
+    m.add_function(wrap_pyfunction!(py_decompress_board, m)?)?;
+    m.add_function(wrap_pyfunction!(position_to_tensor, m)?)?;
+    m.add_function(wrap_pyfunction!(get_policy_logits, m)?)?;
+    m.add_function(wrap_pyfunction!(static_exchange_evaluation, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_board, m)?)?;
+    m.add_function(wrap_pyfunction!(display_board, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_board_with_seed, m)?)?;
+    m.add_function(wrap_pyfunction!(py_batch_positions_to_tensor, m)?)?;
+    m.add_function(wrap_pyfunction!(py_batch_evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(stochastic_best_move, m)?)?;
+    m.add_function(wrap_pyfunction!(py_texel_tune, m)?)?;
+    m.add_function(wrap_pyfunction!(mcts, m)?)?;
+    m.add_function(wrap_pyfunction!(mcts_biased, m)?)?;
+    m.add_function(wrap_pyfunction!(py_board_from_fen, m)?)?;
+    m.add_function(wrap_pyfunction!(py_board_to_fen, m)?)?;
+    m.add_function(wrap_pyfunction!(py_parse_move, m)?)?;
+    m.add_function(wrap_pyfunction!(py_format_move, m)?)?;
+    m.add_function(wrap_pyfunction!(py_get_valid_moves, m)?)?;
+    m.add_function(wrap_pyfunction!(py_get_winner, m)?)?;
+    m.add_function(wrap_pyfunction!(py_game_status, m)?)?;
+    m.add_function(wrap_pyfunction!(py_count_pieces, m)?)?;
+    m.add_function(wrap_pyfunction!(py_get_capture_targets, m)?)?;
+    m.add_function(wrap_pyfunction!(py_piece_difference, m)?)?;
+    m.add_function(wrap_pyfunction!(py_make_move, m)?)?;
+    m.add_function(wrap_pyfunction!(py_validate_board, m)?)?;
+    m.add_function(wrap_pyfunction!(py_board_diff, m)?)?;
+    m.add_function(wrap_pyfunction!(py_starting_board, m)?)?;
+    m.add_function(wrap_pyfunction!(py_is_starting_position, m)?)?;
+    m.add_function(wrap_pyfunction!(py_is_legal_move, m)?)?;
+    Ok(())
+}